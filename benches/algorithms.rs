@@ -0,0 +1,40 @@
+use cache_ro::{Cache, CacheConfig};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::time::Duration;
+use throttle_ro::{Algorithm, ThrottlesService};
+
+fn attempt(c: &mut Criterion) {
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let algorithms = [
+        ("fixed_window", Algorithm::FixedWindow),
+        ("token_bucket", Algorithm::TokenBucket { capacity: 100 }),
+        ("sliding_window_log", Algorithm::SlidingWindowLog),
+        ("sliding_window_counter", Algorithm::SlidingWindowCounter),
+        ("gcra", Algorithm::Gcra { burst: 100 }),
+    ];
+
+    let mut group = c.benchmark_group("attempt");
+    for (name, algorithm) in algorithms {
+        let service = ThrottlesService::new(
+            "127.0.0.1".to_string(),
+            100,
+            Duration::from_secs(1),
+            &format!("bench_{name}_"),
+        )
+        .with_algorithm(algorithm);
+
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| black_box(service.attempt(&cache)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, attempt);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use cache_ro::{Cache, CacheConfig};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+use throttle_ro::ThrottleStore;
+use throttle_ro::ThrottlesService;
+use throttle_ro::store::MemoryStore;
+
+/// Runs `iters` attempts split across `threads` threads hammering the same key
+/// and returns the wall-clock time, so the reported figure is per-check latency
+/// under contention.
+fn contended<S>(store: &S, threads: u64, iters: u64) -> Duration
+where
+    S: ThrottleStore + Sync,
+{
+    let service = ThrottlesService::new(
+        "127.0.0.1".to_string(),
+        u32::MAX,
+        Duration::from_secs(3600),
+        "bench_",
+    );
+    let per_thread = iters.div_ceil(threads);
+
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for _ in 0..per_thread {
+                    black_box(service.attempt(store).allowed);
+                }
+            });
+        }
+    });
+    started.elapsed()
+}
+
+fn attempt_under_contention(c: &mut Criterion) {
+    let memory = MemoryStore::new();
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mut group = c.benchmark_group("contended_attempt");
+    for threads in [1, 4, 16] {
+        group.bench_with_input(
+            BenchmarkId::new("memory_store", threads),
+            &threads,
+            |b, &n| b.iter_custom(|iters| contended(&memory, n, iters)),
+        );
+        group.bench_with_input(BenchmarkId::new("cache_ro", threads), &threads, |b, &n| {
+            b.iter_custom(|iters| contended(&cache, n, iters))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, attempt_under_contention);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+use crate::IpNet;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+/// What an [`AccessList`] says about an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Always admit the address without counting its attempts.
+    Allow,
+    /// Always reject the address.
+    Deny,
+}
+
+/// A set of allowed and denied networks, looked up in a prefix trie.
+///
+/// When an address falls into several ranges, the most specific one wins, so
+/// a single host can be allowed inside a denied subnet and vice versa. A
+/// network listed as both allowed and denied is denied.
+#[derive(Debug, Clone, Default)]
+pub struct AccessRules {
+    v4: Node,
+    v6: Node,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    access: Option<Access>,
+    children: [Option<Box<Node>>; 2],
+}
+
+impl AccessRules {
+    /// Creates an empty rule set that matches no address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always admits addresses in `net`.
+    pub fn allow(mut self, net: IpNet) -> Self {
+        self.insert(net, Access::Allow);
+        self
+    }
+
+    /// Always rejects addresses in `net`.
+    pub fn deny(mut self, net: IpNet) -> Self {
+        self.insert(net, Access::Deny);
+        self
+    }
+
+    /// Returns the access of the most specific network containing `ip`, if any.
+    pub fn get(&self, ip: IpAddr) -> Option<Access> {
+        let (mut node, bits, width) = self.root(ip.to_canonical());
+        let mut access = node.access;
+        for depth in 0..width {
+            match &node.children[bit(bits, width, depth)] {
+                Some(child) => node = child,
+                None => break,
+            }
+            access = node.access.or(access);
+        }
+        access
+    }
+
+    fn insert(&mut self, net: IpNet, access: Access) {
+        let (bits, width) = bits(net.addr());
+        let mut node = match net.addr() {
+            IpAddr::V4(_) => &mut self.v4,
+            IpAddr::V6(_) => &mut self.v6,
+        };
+        for depth in 0..net.prefix_len() as u32 {
+            node = node.children[bit(bits, width, depth)].get_or_insert_default();
+        }
+        if node.access != Some(Access::Deny) {
+            node.access = Some(access);
+        }
+    }
+
+    fn root(&self, ip: IpAddr) -> (&Node, u128, u32) {
+        let (bits, width) = bits(ip);
+        match ip {
+            IpAddr::V4(_) => (&self.v4, bits, width),
+            IpAddr::V6(_) => (&self.v6, bits, width),
+        }
+    }
+}
+
+fn bits(ip: IpAddr) -> (u128, u32) {
+    match ip {
+        IpAddr::V4(ip) => (ip.to_bits() as u128, 32),
+        IpAddr::V6(ip) => (ip.to_bits(), 128),
+    }
+}
+
+fn bit(bits: u128, width: u32, depth: u32) -> usize {
+    (bits >> (width - 1 - depth)) as usize & 1
+}
+
+/// CIDR allow and deny lists evaluated before any attempt is counted.
+///
+/// Allowed addresses always get an allowed [`Decision`](crate::Decision)
+/// without touching the store, and denied ones always get a rejection with no
+/// `retry_after`. Everything else is throttled as usual.
+///
+/// Clones share the same rules, so [`reload`](Self::reload) takes effect in
+/// every service the list was given to.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::{AccessList, AccessRules, ThrottlesService};
+///
+/// let access = AccessList::new(
+///     AccessRules::new()
+///         .allow("10.0.0.0/8".parse().unwrap())
+///         .deny("203.0.113.0/24".parse().unwrap()),
+/// );
+///
+/// let service = ThrottlesService::new("10.1.2.3".to_string(), 5, Duration::from_secs(60), "api_")
+///     .with_access_list(access.clone());
+///
+/// // Later, e.g. when the configuration file changes:
+/// access.reload(AccessRules::new().deny("198.51.100.0/24".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    rules: Arc<RwLock<AccessRules>>,
+}
+
+impl AccessList {
+    /// Creates an access list enforcing `rules`.
+    pub fn new(rules: AccessRules) -> Self {
+        Self {
+            rules: Arc::new(RwLock::new(rules)),
+        }
+    }
+
+    /// Replaces the rules of this list and every clone of it.
+    pub fn reload(&self, rules: AccessRules) {
+        *self.rules.write().unwrap() = rules;
+    }
+
+    /// Returns the access of the most specific network containing `ip`, if any.
+    pub fn get(&self, ip: IpAddr) -> Option<Access> {
+        self.rules.read().unwrap().get(ip)
+    }
+}
@@ -0,0 +1,271 @@
+//! [actix-web](https://docs.rs/actix-web) middleware that throttles requests
+//! by the client address, with separate limits per route.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use actix_web::{App, HttpResponse, web};
+//! use throttle_ro::HeaderDialect;
+//! use throttle_ro::actix::Throttle;
+//! use throttle_ro::store::MemoryStore;
+//!
+//! // 100 requests per minute per client, but only 5 logins.
+//! let throttle = Throttle::new(MemoryStore::new(), 100, Duration::from_secs(60), "api_")
+//!     .with_route("/login", 5, Duration::from_secs(60))
+//!     .with_headers(HeaderDialect::Ietf);
+//!
+//! let app = App::new()
+//!     .wrap(throttle)
+//!     .route("/", web::get().to(HttpResponse::Ok));
+//! ```
+
+use crate::failure::FailureHook;
+use crate::headers::seconds;
+use crate::{
+    AccessList, Algorithm, Decision, FailurePolicy, HeaderDialect, ThrottleError, ThrottleStore,
+    ThrottlesService, TrustedProxies,
+};
+use actix_web::body::EitherBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpResponse};
+use std::future::{Future, Ready, ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A [`Transform`] that throttles requests by the client address.
+///
+/// Requests are limited by the default policy unless their path is one of the
+/// routes given to [`with_route`](Self::with_route) or lies below it, in which
+/// case the longest matching route applies and is counted separately. Routes
+/// match whole path segments: `/login` covers `/login` and `/login/totp`, but
+/// not `/login-history`. Every
+/// request is checked and recorded in one atomic
+/// [`attempt`](ThrottlesService::attempt); rejected requests get a
+/// `429 Too Many Requests` with a `Retry-After` header and never reach the
+/// handler. Requests without a peer address pass through.
+///
+/// Store operations are synchronous, so prefer a fast store such as
+/// [`MemoryStore`](crate::store::MemoryStore) on async runtimes.
+pub struct Throttle<St> {
+    store: Arc<St>,
+    default: ThrottlesService,
+    routes: Vec<(String, ThrottlesService)>,
+    proxies: Option<TrustedProxies>,
+    headers: Option<HeaderDialect>,
+}
+
+impl<St> Throttle<St> {
+    /// Creates a middleware allowing `max_attempts` per `period` for each client.
+    pub fn new(store: St, max_attempts: u32, period: Duration, prefix: &str) -> Self {
+        Self {
+            store: Arc::new(store),
+            default: ThrottlesService::for_key("", max_attempts, period, prefix),
+            routes: Vec::new(),
+            proxies: None,
+            headers: None,
+        }
+    }
+
+    /// Applies a separate limit to requests for `path` and the paths below it.
+    pub fn with_route(mut self, path: &str, max_attempts: u32, period: Duration) -> Self {
+        let service = ThrottlesService {
+            max_attempts,
+            period,
+            ..self.default.clone()
+        };
+        self.routes.push((path.to_string(), service));
+        self.routes
+            .sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        self
+    }
+
+    /// Selects the algorithm used by every route.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.map_services(|service| service.with_algorithm(algorithm));
+        self
+    }
+
+    /// Consults `access` before counting any request.
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.map_services(|service| service.with_access_list(access.clone()));
+        self
+    }
+
+    /// Selects what to answer when the store fails, see [`FailurePolicy`].
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.map_services(|service| service.with_failure_policy(policy));
+        self
+    }
+
+    /// Calls `hook` with every store error the [`FailurePolicy`] handles.
+    pub fn with_failure_hook<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        let hook: FailureHook = Arc::new(hook);
+        self.map_services(|service| service.with_shared_failure_hook(hook.clone()));
+        self
+    }
+
+    /// Returns how many store errors the failure policy has handled, across
+    /// every route.
+    pub fn failures(&self) -> u64 {
+        self.default.failures()
+    }
+
+    /// Takes the client address from forwarding headers sent by `proxies`.
+    pub fn with_trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.proxies = Some(proxies);
+        self
+    }
+
+    /// Adds rate-limit headers in `dialect` to every throttled response.
+    pub fn with_headers(mut self, dialect: HeaderDialect) -> Self {
+        self.headers = Some(dialect);
+        self
+    }
+
+    fn map_services(&mut self, f: impl Fn(ThrottlesService) -> ThrottlesService) {
+        self.default = f(self.default.clone());
+        for (_, service) in &mut self.routes {
+            *service = f(service.clone());
+        }
+    }
+
+    fn client_ip(&self, request: &ServiceRequest) -> Option<IpAddr> {
+        let peer = request.peer_addr()?.ip();
+        Some(match &self.proxies {
+            Some(proxies) => proxies.client_ip(
+                peer,
+                request
+                    .headers()
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+            ),
+            None => peer,
+        })
+    }
+
+    fn service(&self, path: &str, ip: IpAddr) -> ThrottlesService {
+        match self
+            .routes
+            .iter()
+            .find(|(route, _)| matches_route(path, route))
+        {
+            Some((route, service)) => service.rekey(&(route.as_str(), ip)),
+            None => self.default.rekey(&ip),
+        }
+    }
+}
+
+/// Returns whether `path` is `route` or lies below it.
+fn matches_route(path: &str, route: &str) -> bool {
+    match path.strip_prefix(route) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || route.ends_with('/'),
+        None => false,
+    }
+}
+
+impl<St> Clone for Throttle<St> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            default: self.default.clone(),
+            routes: self.routes.clone(),
+            proxies: self.proxies.clone(),
+            headers: self.headers,
+        }
+    }
+}
+
+impl<S, B, St> Transform<S, ServiceRequest> for Throttle<St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    St: ThrottleStore + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ThrottleMiddleware<S, St>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ThrottleMiddleware {
+            service: Rc::new(service),
+            throttle: self.clone(),
+        }))
+    }
+}
+
+/// The middleware service produced by [`Throttle`].
+pub struct ThrottleMiddleware<S, St> {
+    service: Rc<S>,
+    throttle: Throttle<St>,
+}
+
+impl<S, B, St> Service<ServiceRequest> for ThrottleMiddleware<S, St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    St: ThrottleStore + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let throttle = &self.throttle;
+        let Some(ip) = throttle.client_ip(&request) else {
+            let future = self.service.call(request);
+            return Box::pin(async move { Ok(future.await?.map_into_left_body()) });
+        };
+
+        // Match routes on the decoded path actix routes on, so `/%6Cogin`
+        // cannot reach the `/login` handler under the default policy.
+        let service = throttle.service(request.match_info().as_str(), ip);
+        let decision = service.attempt(&*throttle.store);
+        let headers = match throttle.headers {
+            Some(dialect) => service.headers(&decision, dialect),
+            None => Vec::new(),
+        };
+
+        if !decision.allowed {
+            let response = rejection(&decision, headers);
+            return Box::pin(
+                async move { Ok(request.into_response(response).map_into_right_body()) },
+            );
+        }
+
+        let future = self.service.call(request);
+        Box::pin(async move {
+            let mut response = future.await?;
+            for (name, value) in headers {
+                response.headers_mut().insert(
+                    HeaderName::from_bytes(name.as_bytes()).expect("valid header name"),
+                    HeaderValue::try_from(value).expect("valid header value"),
+                );
+            }
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+fn rejection(decision: &Decision, headers: Vec<(&'static str, String)>) -> HttpResponse {
+    let mut response = HttpResponse::TooManyRequests();
+    if let Some(retry_after) = decision.retry_after {
+        response.insert_header(("Retry-After", seconds(retry_after)));
+    }
+    for header in headers {
+        response.insert_header(header);
+    }
+    response.finish()
+}
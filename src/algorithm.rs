@@ -0,0 +1,351 @@
+//! Rate limiting algorithms and the per-key state transitions they perform.
+//!
+//! Every algorithm is a pure function from the stored state (and its remaining
+//! TTL) to the state that should be written back and the resulting [`Decision`].
+//! The service takes care of loading, locking and persisting the state.
+
+use crate::Decision;
+use crate::clock::{saturating_micros, saturating_millis};
+use std::time::Duration;
+
+/// The algorithm a [`ThrottlesService`](crate::ThrottlesService) uses to decide
+/// whether an attempt is allowed.
+///
+/// Every algorithm enforces the service's `max_attempts` per `period` as its
+/// sustained rate and stores its state under the service's cache key.
+///
+/// With `max_attempts` set to 0, every algorithm rejects all attempts with
+/// `retry_after: None`, except [`TokenBucket`](Self::TokenBucket): it never
+/// refills, so it admits its initial `capacity` and nothing after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// Counts attempts in a window that starts with the first hit and lasts `period`.
+    ///
+    /// Cheap, but allows up to twice `max_attempts` in a burst straddling a
+    /// window boundary.
+    #[default]
+    FixedWindow,
+    /// A bucket holding up to `capacity` tokens that refills at `max_attempts`
+    /// tokens per `period`; every attempt takes one token.
+    ///
+    /// Allows short bursts of up to `capacity` attempts while enforcing a smooth
+    /// sustained rate.
+    TokenBucket {
+        /// Maximum number of tokens the bucket can hold.
+        capacity: u32,
+    },
+    /// Stores the timestamp of every recorded attempt and allows `max_attempts`
+    /// in any rolling `period`.
+    ///
+    /// Exact, but memory grows with `max_attempts`.
+    SlidingWindowLog,
+    /// Approximates a rolling window by weighting the previous fixed window's
+    /// count by how much of it still overlaps the rolling `period`.
+    ///
+    /// Constant memory per key, and smooths out boundary bursts.
+    SlidingWindowCounter,
+    /// The generic cell rate algorithm: spaces attempts `period / max_attempts`
+    /// apart while tolerating bursts of up to `burst` attempts.
+    ///
+    /// Stores a single timestamp per key (the theoretical arrival time) and
+    /// yields exact `retry_after` values.
+    Gcra {
+        /// Number of attempts that may be made back to back.
+        burst: u32,
+    },
+    /// A leaky bucket that drains `max_attempts` per `period` and queues
+    /// attempts over that rate instead of rejecting them.
+    ///
+    /// Queued attempts are admitted with a [`Decision::delay`] the caller should
+    /// wait out before proceeding. Once `max_queue` attempts are waiting, further
+    /// attempts are rejected.
+    LeakyBucket {
+        /// Maximum number of attempts that may be waiting at once.
+        max_queue: u32,
+    },
+}
+
+/// What an operation does with the stored state of a key.
+///
+/// Passed to [`ThrottleStore::run_native`](crate::ThrottleStore::run_native).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Only report the status, never write.
+    Check,
+    /// Record the attempt only if it is allowed.
+    Attempt,
+    /// Record the attempt unconditionally.
+    Hit,
+}
+
+impl Op {
+    fn records(self, allowed: bool) -> bool {
+        match self {
+            Op::Check => false,
+            Op::Attempt => allowed,
+            Op::Hit => true,
+        }
+    }
+}
+
+/// The state to write back (with its TTL), if any, and the resulting decision.
+pub(crate) type Transition<S> = (Option<(S, Duration)>, Decision);
+
+/// Converts fractional milliseconds into a duration, rounding up so that
+/// waiting the returned time is always enough.
+fn millis(ms: f64) -> Duration {
+    Duration::from_millis(ms.max(0.0).ceil() as u64)
+}
+
+pub(crate) fn fixed_window(
+    count: Option<u32>,
+    ttl: Option<Duration>,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<u32> {
+    let (count, reset_after) = match (count, ttl) {
+        (Some(count), Some(ttl)) => (count, ttl),
+        _ => (0, period),
+    };
+    let allowed = count.saturating_add(cost) <= limit;
+    let recorded = op.records(allowed);
+    let count = if recorded {
+        count.saturating_add(cost)
+    } else {
+        count
+    };
+
+    let decision = if allowed {
+        Decision::allow(limit, limit.saturating_sub(count), reset_after)
+    } else if cost > limit {
+        Decision::block(limit)
+    } else {
+        Decision::deny(limit, reset_after, reset_after)
+    };
+    (recorded.then_some((count, reset_after)), decision)
+}
+
+/// Token bucket state: `(tokens, updated_at_ms)`.
+pub(crate) type TokenBucketState = (f64, u64);
+
+pub(crate) fn token_bucket(
+    state: Option<TokenBucketState>,
+    now: u64,
+    capacity: u32,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<TokenBucketState> {
+    let rate = limit as f64 / period.as_millis().max(1) as f64;
+    let capacity = capacity as f64;
+    let tokens = match state {
+        Some((tokens, updated_at)) => {
+            (tokens + now.saturating_sub(updated_at) as f64 * rate).min(capacity)
+        }
+        None => capacity,
+    };
+    let cost = cost as f64;
+    let allowed = tokens >= cost;
+    let recorded = op.records(allowed);
+    let left = if recorded { tokens - cost } else { tokens };
+    let reset_after = millis((capacity - left) / rate);
+
+    let decision = if allowed {
+        Decision::allow(capacity as u32, left.max(0.0) as u32, reset_after)
+    } else if cost > capacity {
+        Decision::block(capacity as u32)
+    } else {
+        Decision::deny(capacity as u32, reset_after, millis((cost - tokens) / rate))
+    };
+    let state = recorded.then(|| ((left, now), reset_after.max(Duration::from_millis(1))));
+    (state, decision)
+}
+
+pub(crate) fn sliding_window_log(
+    log: Option<Vec<u64>>,
+    now: u64,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<Vec<u64>> {
+    let period = saturating_millis(period);
+    let mut log = log.unwrap_or_default();
+    log.retain(|&at| at.saturating_add(period) > now);
+    // Entries that must expire before the cost fits.
+    let excess = (log.len() + cost as usize).saturating_sub(limit as usize);
+
+    let allowed = excess == 0;
+    let recorded = op.records(allowed);
+    if recorded {
+        // Entries beyond the limit cannot change any decision, so keep the
+        // newest `limit` of them.
+        let entries = cost.min(limit) as usize;
+        log.extend(std::iter::repeat_n(now, entries));
+        log.drain(..log.len().saturating_sub(limit as usize));
+    }
+
+    let reset_after = log.last().map_or(Duration::ZERO, |&at| {
+        Duration::from_millis(at.saturating_add(period) - now)
+    });
+    let decision = if allowed {
+        Decision::allow(limit, limit.saturating_sub(log.len() as u32), reset_after)
+    } else if cost > limit {
+        Decision::block(limit)
+    } else {
+        let oldest = log[excess - 1];
+        Decision::deny(
+            limit,
+            reset_after,
+            Duration::from_millis(oldest.saturating_add(period) - now),
+        )
+    };
+    let state = recorded.then_some((log, reset_after));
+    (state, decision)
+}
+
+/// Sliding window counter state: `(window_start_ms, previous_count, current_count)`.
+pub(crate) type SlidingWindowCounterState = (u64, u32, u32);
+
+pub(crate) fn sliding_window_counter(
+    state: Option<SlidingWindowCounterState>,
+    now: u64,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<SlidingWindowCounterState> {
+    let period = saturating_millis(period).max(1);
+    let window_start = now - now % period;
+    let window_end = window_start.saturating_add(period);
+    let (previous, current) = match state {
+        Some((start, previous, current)) if start == window_start => (previous, current),
+        Some((start, _, current)) if start.saturating_add(period) == window_start => (current, 0),
+        _ => (0, 0),
+    };
+
+    let elapsed = (now - window_start) as f64;
+    let weight = (period as f64 - elapsed) / period as f64;
+    let estimate = previous as f64 * weight + current as f64;
+
+    // The estimate must stay below this for the whole cost to fit.
+    let threshold = limit.saturating_add(1).saturating_sub(cost);
+    let allowed = estimate < threshold as f64;
+    let recorded = op.records(allowed);
+    let current = if recorded {
+        current.saturating_add(cost)
+    } else {
+        current
+    };
+    let estimate = previous as f64 * weight + current as f64;
+
+    let reset_after = Duration::from_millis(window_end - now);
+    let decision = if allowed {
+        let remaining = (limit as f64 - estimate).max(0.0) as u32;
+        Decision::allow(limit, remaining, reset_after)
+    } else if cost > limit {
+        Decision::block(limit)
+    } else {
+        let retry_after = if current < threshold {
+            // Wait for the previous window's weight to decay enough.
+            let until = period as f64 * (1.0 - (threshold - current) as f64 / previous as f64);
+            millis(until - elapsed + 1.0)
+        } else {
+            // Wait for the current window to become the previous one and decay.
+            let until = period as f64 * (1.0 - threshold as f64 / current as f64);
+            reset_after.saturating_add(millis(until + 1.0))
+        };
+        Decision::deny(limit, reset_after, retry_after)
+    };
+    let ttl = Duration::from_millis(window_end.saturating_add(period) - now);
+    let state = recorded.then_some(((window_start, previous, current), ttl));
+    (state, decision)
+}
+
+pub(crate) fn gcra(
+    tat: Option<u64>,
+    now: u64,
+    burst: u32,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<u64> {
+    if limit == 0 {
+        return (None, Decision::block(burst));
+    }
+
+    // Work in microseconds so the emission interval keeps its precision.
+    let now = now * 1000;
+    let interval = (saturating_micros(period) / limit as u64).max(1);
+    let tolerance = interval.saturating_mul(burst as u64);
+
+    let tat = tat.unwrap_or(now).max(now);
+    let next = tat.saturating_add(interval.saturating_mul(cost as u64));
+    let allow_at = next.saturating_sub(tolerance);
+
+    let allowed = now >= allow_at;
+    let recorded = op.records(allowed);
+    let tat = if recorded { next } else { tat };
+
+    let reset_after = Duration::from_micros(tat - now);
+    let decision = if allowed {
+        let remaining = now.saturating_add(tolerance).saturating_sub(tat) / interval;
+        Decision::allow(burst, remaining as u32, reset_after)
+    } else if cost > burst {
+        Decision::block(burst)
+    } else {
+        Decision::deny(burst, reset_after, Duration::from_micros(allow_at - now))
+    };
+    let state = recorded.then_some((tat, reset_after.max(Duration::from_millis(1))));
+    (state, decision)
+}
+
+pub(crate) fn leaky_bucket(
+    next_free: Option<u64>,
+    now: u64,
+    max_queue: u32,
+    limit: u32,
+    period: Duration,
+    cost: u32,
+    op: Op,
+) -> Transition<u64> {
+    let capacity = max_queue as u64 + 1;
+    if limit == 0 {
+        return (None, Decision::block(capacity as u32));
+    }
+
+    let now = now * 1000;
+    let interval = (saturating_micros(period) / limit as u64).max(1);
+
+    let start = next_free.unwrap_or(now).max(now);
+    let delay = start - now;
+    let queued = delay.div_ceil(interval);
+
+    let allowed = queued + cost as u64 <= capacity;
+    let recorded = op.records(allowed);
+    let next_free = if recorded {
+        start.saturating_add(interval.saturating_mul(cost as u64))
+    } else {
+        start
+    };
+
+    let reset_after = Duration::from_micros(next_free - now);
+    let depth = (next_free - now).div_ceil(interval);
+    let decision = if allowed {
+        let remaining = capacity.saturating_sub(depth);
+        Decision::allow(capacity as u32, remaining as u32, reset_after)
+            .with_delay(Duration::from_micros(delay))
+    } else if cost as u64 > capacity {
+        Decision::block(capacity as u32)
+    } else {
+        let queue = (capacity - cost as u64).saturating_mul(interval);
+        let retry_after = Duration::from_micros(delay.saturating_sub(queue));
+        Decision::deny(capacity as u32, reset_after, retry_after)
+    };
+    let state = recorded.then_some((next_free, reset_after.max(Duration::from_millis(1))));
+    (state, decision)
+}
@@ -0,0 +1,107 @@
+//! Time sources for the limiter.
+//!
+//! Algorithms read the current time from a [`Clock`] instead of the system
+//! clock, so tests can advance time programmatically.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The system wall clock, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same time, so one handle can be given to the service and
+/// the store while the test keeps another to advance it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::ThrottlesService;
+/// use throttle_ro::clock::ManualClock;
+/// use throttle_ro::store::MemoryStore;
+///
+/// let clock = ManualClock::new();
+/// let store = MemoryStore::with_clock(clock.clone());
+/// let service = ThrottlesService::new("127.0.0.1".to_string(), 1, Duration::from_secs(86400), "daily_")
+///     .with_clock(clock.clone());
+///
+/// assert!(service.attempt(&store).allowed);
+/// assert!(!service.attempt(&store).allowed);
+///
+/// clock.advance(Duration::from_secs(86400));
+/// assert!(service.attempt(&store).allowed);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    millis: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Creates a clock frozen at the current system time.
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    /// Creates a clock frozen at `time`.
+    pub fn starting_at(time: SystemTime) -> Self {
+        Self {
+            millis: Arc::new(AtomicU64::new(unix_millis(time))),
+        }
+    }
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.millis
+            .fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Moves the clock to `time`.
+    pub fn set(&self, time: SystemTime) {
+        self.millis.store(unix_millis(time), Ordering::SeqCst);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.millis.load(Ordering::SeqCst))
+    }
+}
+
+/// Converts `duration` to whole milliseconds, saturating at `u64::MAX`.
+pub(crate) fn saturating_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Converts `duration` to whole microseconds, saturating at `u64::MAX`.
+pub(crate) fn saturating_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
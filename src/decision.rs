@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+/// The rate-limit status of a key, computed in one pass over its stored state.
+///
+/// Returned by [`ThrottlesService::check`](crate::ThrottlesService::check) and
+/// [`ThrottlesService::attempt`](crate::ThrottlesService::attempt), and carries
+/// everything needed to build a `429 Too Many Requests` response.
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    /// Whether the attempt is (or was) admitted.
+    pub allowed: bool,
+    /// The configured maximum number of attempts per window.
+    pub limit: u32,
+    /// Attempts still available in the current window.
+    pub remaining: u32,
+    /// Time until the current window resets.
+    pub reset_after: Duration,
+    /// How long the caller should wait before retrying, when rejected.
+    ///
+    /// `None` on a rejection means the key is blocked outright, e.g. by an
+    /// [`AccessList`](crate::AccessList), and retrying will not help.
+    pub retry_after: Option<Duration>,
+    /// How long an admitted attempt should wait before proceeding.
+    ///
+    /// Always zero except for [`Algorithm::LeakyBucket`](crate::Algorithm::LeakyBucket),
+    /// which queues attempts over the rate instead of rejecting them.
+    pub delay: Duration,
+}
+
+impl Decision {
+    pub(crate) fn allow(limit: u32, remaining: u32, reset_after: Duration) -> Self {
+        Self {
+            allowed: true,
+            limit,
+            remaining,
+            reset_after,
+            retry_after: None,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn deny(limit: u32, reset_after: Duration, retry_after: Duration) -> Self {
+        Self {
+            allowed: false,
+            limit,
+            remaining: 0,
+            reset_after,
+            retry_after: Some(retry_after),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn block(limit: u32) -> Self {
+        Self {
+            allowed: false,
+            limit,
+            remaining: 0,
+            reset_after: Duration::ZERO,
+            retry_after: None,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
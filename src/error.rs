@@ -0,0 +1,77 @@
+use std::error::Error;
+use std::fmt;
+
+/// A boxed error from an underlying library.
+type Source = Box<dyn Error + Send + Sync>;
+
+/// The error returned by the fallible `try_` methods and by storage backends.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ThrottleError {
+    /// The store could not be read or written, e.g. a full disk or a lost
+    /// connection.
+    Storage(Source),
+    /// Throttling state could not be encoded or decoded.
+    Serialization(Source),
+    /// The limiter or its store is misconfigured, e.g. an invalid store URL.
+    Config(Source),
+}
+
+impl ThrottleError {
+    /// Wraps an error returned by [`cache_ro`].
+    ///
+    /// `cache_ro` reports I/O failures of its persistent storage as
+    /// [`std::io::Error`] and everything else is an encoding failure. Its
+    /// errors are not thread-safe, so the latter are kept by message only.
+    #[cfg(feature = "cache-ro")]
+    pub(crate) fn from_cache(error: Box<dyn Error>) -> Self {
+        match error.downcast::<std::io::Error>() {
+            Ok(error) => Self::Storage(error),
+            Err(error) => Self::Serialization(error.to_string().into()),
+        }
+    }
+}
+
+impl fmt::Display for ThrottleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Storage(error) => write!(f, "throttle store failed: {error}"),
+            Self::Serialization(error) => write!(f, "throttle state serialization failed: {error}"),
+            Self::Config(error) => write!(f, "invalid throttle configuration: {error}"),
+        }
+    }
+}
+
+impl Error for ThrottleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Storage(error) | Self::Serialization(error) | Self::Config(error) => {
+                Some(error.as_ref())
+            }
+        }
+    }
+}
+
+/// Treats errors of custom stores as storage failures.
+impl From<Source> for ThrottleError {
+    fn from(error: Source) -> Self {
+        Self::Storage(error)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for ThrottleError {
+    fn from(error: redis::RedisError) -> Self {
+        match error.kind() {
+            redis::ErrorKind::InvalidClientConfig => Self::Config(Box::new(error)),
+            _ => Self::Storage(Box::new(error)),
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<serde_json::Error> for ThrottleError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serialization(Box::new(error))
+    }
+}
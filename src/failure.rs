@@ -0,0 +1,24 @@
+use crate::ThrottleError;
+use std::sync::Arc;
+
+/// What a limiter answers when its store fails.
+///
+/// Applies to the methods that cannot return an error, such as
+/// [`check`](crate::ThrottlesService::check) and
+/// [`attempt`](crate::ThrottlesService::attempt); the `try_` methods always
+/// hand the error back instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Panic with the store error.
+    #[default]
+    Panic,
+    /// Admit every attempt while the store is down, e.g. for public read APIs.
+    /// Hits and removals are skipped.
+    Open,
+    /// Reject every attempt while the store is down, e.g. for login or OTP
+    /// endpoints. Rejections ask the caller to retry after one period.
+    Closed,
+}
+
+/// A callback invoked with every store error a [`FailurePolicy`] handles.
+pub(crate) type FailureHook = Arc<dyn Fn(&ThrottleError) + Send + Sync>;
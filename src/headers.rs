@@ -0,0 +1,73 @@
+use crate::Decision;
+use std::time::Duration;
+
+/// Which family of rate-limit response headers to emit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderDialect {
+    /// `RateLimit-Policy` and `RateLimit` from the IETF HTTPAPI draft.
+    #[default]
+    Ietf,
+    /// `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`.
+    Legacy,
+    /// Both of the above, for clients that only understand one of them.
+    Both,
+}
+
+/// Renders `decision` into HTTP response headers as `(name, value)` pairs.
+///
+/// The policy advertises the sustained rate of `quota` attempts per `window`,
+/// i.e. the service's `max_attempts` and `period`. The other headers report
+/// the decision itself, so for bucket algorithms `X-RateLimit-Limit` is the
+/// burst size in [`Decision::limit`]. Reset times are relative, in whole
+/// seconds rounded up. `Retry-After` is added to rejections that can be
+/// retried, whatever the dialect.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::store::MemoryStore;
+/// use throttle_ro::{HeaderDialect, ThrottlesService, rate_limit_headers};
+///
+/// let store = MemoryStore::new();
+/// let period = Duration::from_secs(60);
+/// let service = ThrottlesService::new("127.0.0.1".to_string(), 100, period, "api_");
+///
+/// let decision = service.attempt(&store);
+/// let headers = rate_limit_headers(&decision, 100, period, HeaderDialect::Ietf);
+/// assert_eq!(headers[0], ("RateLimit-Policy", "\"default\";q=100;w=60".to_string()));
+/// assert_eq!(headers[1], ("RateLimit", "\"default\";r=99;t=60".to_string()));
+/// ```
+pub fn rate_limit_headers(
+    decision: &Decision,
+    quota: u32,
+    window: Duration,
+    dialect: HeaderDialect,
+) -> Vec<(&'static str, String)> {
+    let reset = seconds(decision.reset_after);
+    let mut headers = Vec::new();
+    if dialect != HeaderDialect::Legacy {
+        headers.push((
+            "RateLimit-Policy",
+            format!("\"default\";q={};w={}", quota, seconds(window)),
+        ));
+        headers.push((
+            "RateLimit",
+            format!("\"default\";r={};t={}", decision.remaining, reset),
+        ));
+    }
+    if dialect != HeaderDialect::Ietf {
+        headers.push(("X-RateLimit-Limit", decision.limit.to_string()));
+        headers.push(("X-RateLimit-Remaining", decision.remaining.to_string()));
+        headers.push(("X-RateLimit-Reset", reset.to_string()));
+    }
+    if let Some(retry_after) = decision.retry_after {
+        headers.push(("Retry-After", seconds(retry_after).to_string()));
+    }
+    headers
+}
+
+/// Whole seconds in `duration`, rounded up so clients never retry too early.
+pub(crate) fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// How many leading bits of an IP address identify a client.
+///
+/// A single host usually controls a whole IPv6 /64 (or more), so limiting per
+/// full address would hand an attacker 2^64 buckets. Addresses are masked down
+/// to their network prefix before being used as a key.
+///
+/// The default keeps IPv4 addresses whole and groups IPv6 addresses by /64.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::IpAddr;
+/// use throttle_ro::IpAggregation;
+///
+/// let per_subnet = IpAggregation::new(24, 56);
+/// let ip: IpAddr = "203.0.113.77".parse().unwrap();
+/// assert_eq!(per_subnet.key(ip), "203.0.113.0/24");
+///
+/// let ip: IpAddr = "2001:db8:0:1::1".parse().unwrap();
+/// assert_eq!(IpAggregation::default().key(ip), "2001:db8:0:1::/64");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpAggregation {
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+}
+
+impl IpAggregation {
+    /// Limits every address on its own.
+    pub const NONE: Self = Self {
+        ipv4_prefix: 32,
+        ipv6_prefix: 128,
+    };
+
+    /// Creates an aggregation that groups IPv4 addresses by `/ipv4_prefix` and
+    /// IPv6 addresses by `/ipv6_prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ipv4_prefix` exceeds 32 or `ipv6_prefix` exceeds 128.
+    pub fn new(ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        assert!(ipv4_prefix <= 32, "IPv4 prefix length must be at most 32");
+        assert!(ipv6_prefix <= 128, "IPv6 prefix length must be at most 128");
+        Self {
+            ipv4_prefix,
+            ipv6_prefix,
+        }
+    }
+
+    /// Canonicalizes `ip` and masks it down to its network address.
+    ///
+    /// IPv4-mapped IPv6 addresses such as `::ffff:127.0.0.1` are treated as the
+    /// IPv4 address they carry.
+    pub fn apply(&self, ip: IpAddr) -> IpAddr {
+        let ip = ip.to_canonical();
+        match ip {
+            IpAddr::V4(_) => network(ip, self.ipv4_prefix),
+            IpAddr::V6(_) => network(ip, self.ipv6_prefix),
+        }
+    }
+
+    /// Returns the identifier used in cache keys for `ip`.
+    ///
+    /// Whole addresses are written as-is, aggregated ones in CIDR notation, so
+    /// the same client always maps to the same bucket.
+    pub fn key(&self, ip: IpAddr) -> String {
+        let network = self.apply(ip);
+        let (prefix, full) = match network {
+            IpAddr::V4(_) => (self.ipv4_prefix, 32),
+            IpAddr::V6(_) => (self.ipv6_prefix, 128),
+        };
+        if prefix == full {
+            network.to_string()
+        } else {
+            format!("{}/{}", network, prefix)
+        }
+    }
+}
+
+impl Default for IpAggregation {
+    fn default() -> Self {
+        Self::new(32, 64)
+    }
+}
+
+/// Masks `ip` down to its first `prefix` bits.
+fn network(ip: IpAddr, prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(ip.to_bits() & mask))
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(ip.to_bits() & mask))
+        }
+    }
+}
+
+/// An IPv4 or IPv6 network in CIDR notation, such as `10.0.0.0/8`.
+///
+/// The address is masked down to the network on construction, and
+/// IPv4-mapped IPv6 networks are stored as the IPv4 network they carry.
+///
+/// # Examples
+///
+/// ```
+/// use throttle_ro::IpNet;
+///
+/// let net: IpNet = "192.168.1.77/24".parse().unwrap();
+/// assert_eq!(net.to_string(), "192.168.1.0/24");
+/// assert!(net.contains("192.168.1.200".parse().unwrap()));
+///
+/// // A bare address is a single-host network.
+/// let host: IpNet = "2001:db8::1".parse().unwrap();
+/// assert_eq!(host.prefix_len(), 128);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Creates the network of `addr` with a `prefix_len`-bit prefix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix_len` exceeds 32 for IPv4 or 128 for IPv6 addresses.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        let width = max_prefix_len(addr);
+        assert!(prefix_len <= width, "prefix length must be at most {width}");
+
+        let (addr, prefix_len) = match addr {
+            IpAddr::V6(v6) if prefix_len >= 96 => match v6.to_ipv4_mapped() {
+                Some(v4) => (IpAddr::V4(v4), prefix_len - 96),
+                None => (addr, prefix_len),
+            },
+            _ => (addr, prefix_len),
+        };
+        Self {
+            addr: network(addr, prefix_len),
+            prefix_len,
+        }
+    }
+
+    /// Returns the network address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the number of leading bits that identify the network.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether `ip` belongs to this network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.addr.is_ipv4() && network(ip, self.prefix_len) == self.addr
+    }
+}
+
+impl From<IpAddr> for IpNet {
+    fn from(addr: IpAddr) -> Self {
+        Self::new(addr, max_prefix_len(addr))
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = ParseIpNetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| ParseIpNetError)?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| ParseIpNetError)?,
+            None => max_prefix_len(addr),
+        };
+        if prefix_len > max_prefix_len(addr) {
+            return Err(ParseIpNetError);
+        }
+        Ok(Self::new(addr, prefix_len))
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+fn max_prefix_len(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// The error returned when parsing an invalid [`IpNet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIpNetError;
+
+impl fmt::Display for ParseIpNetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid CIDR network")
+    }
+}
+
+impl std::error::Error for ParseIpNetError {}
@@ -0,0 +1,169 @@
+use crate::IpAggregation;
+use std::net::IpAddr;
+
+/// Something a limit can be keyed by: an IP address, a user ID, an API token,
+/// a route, or a tuple combining several of them.
+///
+/// Implemented for strings, integers, [`IpAddr`] (aggregated with the default
+/// [`IpAggregation`]), references and tuples of up to four keys.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::{KeyBuilder, ThrottleKey, ThrottlesService};
+///
+/// // Limit each user on each endpoint separately.
+/// let service = ThrottlesService::for_key(&(42u64, "/export"), 10, Duration::from_secs(60), "api_");
+///
+/// // Or implement the trait for your own types.
+/// struct ApiToken(String);
+///
+/// impl ThrottleKey for ApiToken {
+///     fn append_to(&self, key: &mut KeyBuilder) {
+///         key.part("token").part(&self.0);
+///     }
+/// }
+/// ```
+pub trait ThrottleKey {
+    /// Appends the components identifying this key.
+    fn append_to(&self, key: &mut KeyBuilder);
+
+    /// Returns the client address this key belongs to, if any.
+    ///
+    /// Used to match the key against an [`AccessList`](crate::AccessList).
+    /// [`IpAddr`] returns itself and tuples return their first address. Strings
+    /// return `None` even when they parse as an address, so a username or token
+    /// that looks like one is never exempted or blocked by the list.
+    fn ip(&self) -> Option<IpAddr> {
+        None
+    }
+}
+
+/// Encodes key components into a cache key that cannot collide.
+///
+/// Every component is written as its byte length, a colon and the component
+/// itself, so `("a1", "2")` and `("a", "12")` produce different keys.
+///
+/// ```
+/// use throttle_ro::KeyBuilder;
+///
+/// let mut key = KeyBuilder::new();
+/// key.part("api_").part("127.0.0.1");
+/// assert_eq!(key.build(), "4:api_9:127.0.0.1");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyBuilder {
+    encoded: String,
+}
+
+impl KeyBuilder {
+    /// Creates an empty key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a single component.
+    pub fn part(&mut self, part: &str) -> &mut Self {
+        self.encoded.push_str(&part.len().to_string());
+        self.encoded.push(':');
+        self.encoded.push_str(part);
+        self
+    }
+
+    /// Appends every component of `key`.
+    pub fn key<K: ThrottleKey + ?Sized>(&mut self, key: &K) -> &mut Self {
+        key.append_to(self);
+        self
+    }
+
+    /// Returns the encoded key.
+    pub fn build(&self) -> String {
+        self.encoded.clone()
+    }
+}
+
+impl ThrottleKey for str {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        key.part(self);
+    }
+}
+
+impl ThrottleKey for String {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        key.part(self);
+    }
+}
+
+/// A string documented to hold an address, as taken by the `new`
+/// constructors, keyed like any string but matched against access lists when
+/// it parses.
+pub(crate) struct AddressString<'a>(pub(crate) &'a str);
+
+impl ThrottleKey for AddressString<'_> {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        key.part(self.0);
+    }
+
+    fn ip(&self) -> Option<IpAddr> {
+        self.0.parse().ok()
+    }
+}
+
+impl ThrottleKey for IpAddr {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        key.part(&IpAggregation::default().key(*self));
+    }
+
+    fn ip(&self) -> Option<IpAddr> {
+        Some(*self)
+    }
+}
+
+impl<K: ThrottleKey + ?Sized> ThrottleKey for &K {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        (**self).append_to(key);
+    }
+
+    fn ip(&self) -> Option<IpAddr> {
+        (**self).ip()
+    }
+}
+
+macro_rules! impl_throttle_key_for_integers {
+    ($($ty:ty),*) => {
+        $(
+            impl ThrottleKey for $ty {
+                fn append_to(&self, key: &mut KeyBuilder) {
+                    key.part(&self.to_string());
+                }
+            }
+        )*
+    };
+}
+
+impl_throttle_key_for_integers!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+macro_rules! impl_throttle_key_for_tuples {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: ThrottleKey),+> ThrottleKey for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn append_to(&self, key: &mut KeyBuilder) {
+                    let ($($name,)+) = self;
+                    $($name.append_to(key);)+
+                }
+
+                #[allow(non_snake_case)]
+                fn ip(&self) -> Option<IpAddr> {
+                    let ($($name,)+) = self;
+                    None$(.or_else(|| $name.ip()))+
+                }
+            }
+        )*
+    };
+}
+
+impl_throttle_key_for_tuples!((A, B), (A, B, C), (A, B, C, D));
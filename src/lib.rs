@@ -4,6 +4,8 @@
 //! # Example: Basic Rate Limiting
 //!
 //! ```
+//! # #[cfg(feature = "cache-ro")]
+//! # fn main() {
 //! use std::time::Duration;
 //! use cache_ro::Cache;
 //! use throttle_ro::ThrottlesService;
@@ -24,10 +26,8 @@
 //!     "api_rate_limit_" // cache key prefix
 //! );
 //!
-//! // Check if the IP is allowed to proceed
-//! if service.can_go(&cache) {
-//!     // Record the attempt
-//!     service.hit(&cache);
+//! // Check the limit and record the attempt in one atomic step
+//! if service.attempt(&cache).allowed {
 //!     println!("Request allowed");
 //!     // Process the request...
 //! } else {
@@ -37,46 +37,158 @@
 //!
 //! // You can also manually clear the throttle if needed
 //! // service.remove(&cache);
+//! # }
+//! # #[cfg(not(feature = "cache-ro"))]
+//! # fn main() {}
 //! ```
 //!
 
+#![cfg_attr(docsrs, feature(doc_cfg))]
 
-use cache_ro::Cache;
+mod access;
+#[cfg(feature = "actix")]
+#[cfg_attr(docsrs, doc(cfg(feature = "actix")))]
+pub mod actix;
+mod algorithm;
+pub mod clock;
+mod decision;
+mod error;
+mod failure;
+mod headers;
+mod ip;
+mod key;
+#[cfg(feature = "cache-ro")]
+mod lock;
+mod lockout;
+mod login;
+mod proxy;
+pub mod store;
+mod tiered;
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub mod tower;
+mod wait;
+
+use clock::{Clock, SystemClock};
+use failure::FailureHook;
+use key::AddressString;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
 use std::time::Duration;
+use store::Op;
+
+pub use access::{Access, AccessList, AccessRules};
+pub use algorithm::Algorithm;
+pub use decision::Decision;
+pub use error::ThrottleError;
+pub use failure::FailurePolicy;
+pub use headers::{HeaderDialect, rate_limit_headers};
+pub use ip::{IpAggregation, IpNet, ParseIpNetError};
+pub use key::{KeyBuilder, ThrottleKey};
+pub use lockout::Lockout;
+pub use login::{LoginCounter, LoginDecision, LoginGuard};
+pub use proxy::{ForwardedHeader, TrustedProxies};
+#[cfg(feature = "async")]
+pub use store::AsyncThrottleStore;
+pub use store::{StoreError, StoreValue, ThrottleStore};
+pub use tiered::{TieredDecision, TieredThrottle};
+pub use wait::Wait;
+
+/// Runs the transition of `$service`'s algorithm, binding it to `$f` for
+/// `$update` to apply to the store.
+macro_rules! transition {
+    ($service:ident, $op:ident, $cost:ident, $now:ident, |$f:ident| $update:expr) => {{
+        let (limit, period, op, cost, now) =
+            ($service.max_attempts, $service.period, $op, $cost, $now);
+        match $service.algorithm {
+            Algorithm::FixedWindow => {
+                let $f =
+                    move |count, ttl| algorithm::fixed_window(count, ttl, limit, period, cost, op);
+                $update
+            }
+            Algorithm::TokenBucket { capacity } => {
+                let $f = move |state, _| {
+                    algorithm::token_bucket(state, now, capacity, limit, period, cost, op)
+                };
+                $update
+            }
+            Algorithm::SlidingWindowLog => {
+                let $f =
+                    move |log, _| algorithm::sliding_window_log(log, now, limit, period, cost, op);
+                $update
+            }
+            Algorithm::SlidingWindowCounter => {
+                let $f = move |state, _| {
+                    algorithm::sliding_window_counter(state, now, limit, period, cost, op)
+                };
+                $update
+            }
+            Algorithm::Gcra { burst } => {
+                let $f = move |tat, _| algorithm::gcra(tat, now, burst, limit, period, cost, op);
+                $update
+            }
+            Algorithm::LeakyBucket { max_queue } => {
+                let $f = move |next_free, _| {
+                    algorithm::leaky_bucket(next_free, now, max_queue, limit, period, cost, op)
+                };
+                $update
+            }
+        }
+    }};
+}
 
 /// A service for throttling attempts from an IP address.
 ///
 /// Tracks the number of attempts (hits) from a given IP address and determines
 /// whether further attempts should be allowed based on configured limits.
+/// Limits can also be keyed by a user, an API token or any other
+/// [`ThrottleKey`] with [`for_key`](Self::for_key).
 ///
 /// # Examples
 ///
 /// ```
+/// # #[cfg(feature = "cache-ro")]
+/// # fn main() {
 /// use std::time::Duration;
 /// use cache_ro::Cache;
 /// use throttle_ro::ThrottlesService;
 ///
-/// let cache = Cache::new(Default::default()); // In real usage, configure properly
+/// let cache = Cache::new(cache_ro::CacheConfig {
+///     persistent: false,
+///     ..Default::default()
+/// }).unwrap(); // In real usage, configure properly
 /// let ip = "127.0.0.1".to_string();
-/// let mut service = ThrottlesService::new(
+/// let service = ThrottlesService::new(
 ///     ip,
 ///     5, // max attempts
 ///     Duration::from_secs(60), // time window
 ///     "rate_limit_"
 /// );
 ///
-/// if service.can_go(&cache) {
-///     service.hit(&cache);
+/// if service.attempt(&cache).allowed {
 ///     // Process the request
 /// } else {
 ///     // Reject the request - rate limit exceeded
 /// }
+/// # }
+/// # #[cfg(not(feature = "cache-ro"))]
+/// # fn main() {}
 /// ```
+#[derive(Clone)]
 pub struct ThrottlesService {
-    ip: String,
+    identifier: String,
     max_attempts: u32,
     period: Duration,
     prefix: String,
+    algorithm: Algorithm,
+    clock: Arc<dyn Clock>,
+    ip: Option<IpAddr>,
+    access: Option<AccessList>,
+    failure_policy: FailurePolicy,
+    failure_hook: Option<FailureHook>,
+    failures: Arc<AtomicU64>,
 }
 
 impl ThrottlesService {
@@ -89,57 +201,665 @@ impl ThrottlesService {
     /// * `period` - Duration of the throttling window
     /// * `prefix` - Prefix for cache keys to avoid collisions
     pub fn new(ip: String, max_attempts: u32, period: Duration, prefix: &str) -> Self {
+        Self::for_key(&AddressString(&ip), max_attempts, period, prefix)
+    }
+
+    /// Creates a `ThrottlesService` keyed by anything implementing [`ThrottleKey`].
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::ThrottlesService;
+    ///
+    /// let user_id = 42u64;
+    /// let per_user = ThrottlesService::for_key(&user_id, 100, Duration::from_secs(60), "user_");
+    /// let per_route = ThrottlesService::for_key(&(user_id, "/search"), 10, Duration::from_secs(60), "route_");
+    /// ```
+    pub fn for_key<K: ThrottleKey + ?Sized>(
+        key: &K,
+        max_attempts: u32,
+        period: Duration,
+        prefix: &str,
+    ) -> Self {
         Self {
-            ip,
+            identifier: KeyBuilder::new().key(key).build(),
             max_attempts,
             period,
             prefix: prefix.to_string(),
+            algorithm: Algorithm::default(),
+            clock: Arc::new(SystemClock),
+            ip: key.ip(),
+            access: None,
+            failure_policy: FailurePolicy::default(),
+            failure_hook: None,
+            failures: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    /// Checks whether the IP is allowed to make another attempt.
+    /// Returns a copy of this service with the same limits for another key.
+    pub(crate) fn rekey<K: ThrottleKey + ?Sized>(&self, key: &K) -> Self {
+        Self {
+            identifier: KeyBuilder::new().key(key).build(),
+            ip: key.ip(),
+            ..self.clone()
+        }
+    }
+
+    /// Creates a `ThrottlesService` for a parsed IP address.
     ///
-    /// Returns `true` if the current attempt count is below the maximum allowed.
-    pub fn can_go(&mut self, cache: &Cache) -> bool {
-        let v = self.get_value(cache).unwrap_or(0);
-        v < self.max_attempts
+    /// The address is canonicalized and aggregated with the default
+    /// [`IpAggregation`] (whole IPv4 addresses, IPv6 /64 networks), so all
+    /// spellings of an address, and all addresses of one IPv6 subnet, share a
+    /// bucket. For other prefix lengths, use [`for_ip_with`](Self::for_ip_with).
+    ///
+    /// ```
+    /// use std::net::IpAddr;
+    /// use std::time::Duration;
+    /// use throttle_ro::ThrottlesService;
+    ///
+    /// let a: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+    /// let b: IpAddr = "127.0.0.1".parse().unwrap();
+    /// let period = Duration::from_secs(60);
+    ///
+    /// assert_eq!(
+    ///     ThrottlesService::for_ip(a, 5, period, "api_").key(),
+    ///     ThrottlesService::for_ip(b, 5, period, "api_").key(),
+    /// );
+    /// ```
+    pub fn for_ip(ip: IpAddr, max_attempts: u32, period: Duration, prefix: &str) -> Self {
+        Self::for_key(&ip, max_attempts, period, prefix)
+    }
+
+    /// Creates a `ThrottlesService` for a parsed IP address, aggregated with
+    /// `aggregation`.
+    ///
+    /// The service still knows the full address, so an
+    /// [`AccessList`](Self::with_access_list) matches it as usual.
+    ///
+    /// ```
+    /// use std::net::IpAddr;
+    /// use std::time::Duration;
+    /// use throttle_ro::{IpAggregation, ThrottlesService};
+    ///
+    /// let a: IpAddr = "203.0.113.1".parse().unwrap();
+    /// let b: IpAddr = "203.0.113.200".parse().unwrap();
+    /// let per_subnet = IpAggregation::new(24, 56);
+    /// let period = Duration::from_secs(60);
+    ///
+    /// assert_eq!(
+    ///     ThrottlesService::for_ip_with(a, per_subnet, 5, period, "api_").key(),
+    ///     ThrottlesService::for_ip_with(b, per_subnet, 5, period, "api_").key(),
+    /// );
+    /// ```
+    pub fn for_ip_with(
+        ip: IpAddr,
+        aggregation: IpAggregation,
+        max_attempts: u32,
+        period: Duration,
+        prefix: &str,
+    ) -> Self {
+        Self {
+            identifier: KeyBuilder::new().part(&aggregation.key(ip)).build(),
+            ..Self::for_ip(ip, max_attempts, period, prefix)
+        }
+    }
+
+    /// Selects the algorithm used to enforce the limit.
+    ///
+    /// Defaults to [`Algorithm::FixedWindow`].
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::{Algorithm, ThrottlesService};
+    ///
+    /// // Sustain 10 requests per second, with bursts of up to 50.
+    /// let service = ThrottlesService::new(
+    ///     "127.0.0.1".to_string(),
+    ///     10,
+    ///     Duration::from_secs(1),
+    ///     "api_",
+    /// )
+    /// .with_algorithm(Algorithm::TokenBucket { capacity: 50 });
+    /// ```
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the clock the algorithms read the current time from.
+    ///
+    /// Defaults to [`SystemClock`]. Store-side expiration follows the store's
+    /// own clock, so tests should give the same [`ManualClock`](clock::ManualClock)
+    /// to a [`MemoryStore`](store::MemoryStore). Algorithms that a store runs
+    /// natively, such as the Redis scripts, use the server's clock instead.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Consults `access` before counting any attempt.
+    ///
+    /// Allowed addresses are always admitted with the full quota remaining and
+    /// nothing recorded; denied ones are always rejected with no `retry_after`.
+    /// Only applies when the key carries an address, see [`ThrottleKey::ip`].
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    /// Selects what to answer when the store fails, see [`FailurePolicy`].
+    ///
+    /// Defaults to [`FailurePolicy::Panic`].
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::{FailurePolicy, ThrottlesService};
+    ///
+    /// // Never let a broken store open the door to password guessing.
+    /// let login = ThrottlesService::new("127.0.0.1".to_string(), 5, Duration::from_secs(300), "login_")
+    ///     .with_failure_policy(FailurePolicy::Closed)
+    ///     .with_failure_hook(|error| eprintln!("login limiter degraded: {error}"));
+    /// ```
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    /// Calls `hook` with every store error the [`FailurePolicy`] handles, e.g.
+    /// to log it or raise an alert.
+    pub fn with_failure_hook<F>(self, hook: F) -> Self
+    where
+        F: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        self.with_shared_failure_hook(Arc::new(hook))
+    }
+
+    /// Like [`with_failure_hook`](Self::with_failure_hook), for a hook shared
+    /// by several services.
+    pub(crate) fn with_shared_failure_hook(mut self, hook: FailureHook) -> Self {
+        self.failure_hook = Some(hook);
+        self
+    }
+
+    /// Returns how many store errors the [`FailurePolicy`] has handled, across
+    /// this service and its clones.
+    ///
+    /// A growing count means the limiter is running degraded.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
     }
 
-    fn get_value(&mut self, cache: &Cache) -> Option<u32> {
-        cache.get::<u32>(&self.key())
+    /// Checks whether the IP is allowed to make another attempt.
+    ///
+    /// Returns `true` if the current attempt count is below the maximum allowed.
+    ///
+    /// This only reads the counter; pairing it with [`hit`](Self::hit) is racy
+    /// under concurrent requests. Prefer [`attempt`](Self::attempt).
+    pub fn can_go<S: ThrottleStore>(&mut self, store: &S) -> bool {
+        self.check(store).allowed
     }
 
     /// Generates the cache key for this IP.
+    ///
+    /// The prefix and every key component are length-prefixed (see
+    /// [`KeyBuilder`]), so different prefixes and identifiers never collide.
     pub fn key(&self) -> String {
-        format!("{}{}", self.prefix, self.ip)
+        let mut key = KeyBuilder::new();
+        key.part(&self.prefix);
+        key.build() + &self.identifier
     }
 
     /// Gets the remaining duration for the current throttling window.
     ///
-    /// Returns the configured period if no expiration is set in the cache.
-    pub fn get_expire(&mut self, cache: &Cache) -> Duration {
-        let ex = cache.expire(&self.key());
-        match ex {
+    /// Returns the configured period if no expiration is set in the store.
+    pub fn get_expire<S: ThrottleStore>(&mut self, store: &S) -> Duration {
+        self.try_get_expire(store).unwrap_or_else(|error| {
+            let _ = self.fail(error);
+            self.period
+        })
+    }
+
+    /// Like [`get_expire`](Self::get_expire), but returns store errors
+    /// instead of applying the failure policy.
+    pub fn try_get_expire<S: ThrottleStore>(&self, store: &S) -> Result<Duration, ThrottleError> {
+        let ex = store.ttl(&self.key())?;
+        Ok(match ex {
             None => self.period,
             Some(a) => a,
+        })
+    }
+
+    /// Reports the current status of the IP without recording an attempt.
+    ///
+    /// `remaining` is the number of attempts still available, and `retry_after`
+    /// is set to the time left in the window once the limit is reached.
+    pub fn check<S: ThrottleStore>(&self, store: &S) -> Decision {
+        self.try_check(store)
+            .unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`check`](Self::check), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_check<S: ThrottleStore>(&self, store: &S) -> Result<Decision, ThrottleError> {
+        self.run(store, Op::Check, 1)
+    }
+
+    /// Checks the limit and records the attempt in a single atomic step.
+    ///
+    /// The attempt is only counted when it is allowed, so rejected requests do
+    /// not extend the lockout. Concurrent calls for the same key are serialized,
+    /// which guarantees that no more than `max_attempts` are admitted per window.
+    /// The returned `remaining` already accounts for this attempt.
+    pub fn attempt<S: ThrottleStore>(&self, store: &S) -> Decision {
+        self.try_attempt(store)
+            .unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`attempt`](Self::attempt), but returns store errors instead of
+    /// applying the failure policy.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::store::MemoryStore;
+    /// use throttle_ro::{ThrottleError, ThrottlesService};
+    ///
+    /// fn admit(service: &ThrottlesService, store: &MemoryStore) -> Result<bool, ThrottleError> {
+    ///     Ok(service.try_attempt(store)?.allowed)
+    /// }
+    /// ```
+    pub fn try_attempt<S: ThrottleStore>(&self, store: &S) -> Result<Decision, ThrottleError> {
+        self.run(store, Op::Attempt, 1)
+    }
+
+    /// Like [`attempt`](Self::attempt), but consumes `cost` attempts at once,
+    /// e.g. to charge an export more than a cheap read.
+    ///
+    /// The attempt is only admitted, and only recorded, when the whole cost
+    /// fits. A cost larger than the algorithm's capacity ([`Decision::limit`])
+    /// can never fit and is rejected with `retry_after: None`.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::ThrottlesService;
+    /// use throttle_ro::store::MemoryStore;
+    ///
+    /// let store = MemoryStore::new();
+    /// let service = ThrottlesService::new("127.0.0.1".to_string(), 10, Duration::from_secs(60), "api_");
+    ///
+    /// assert_eq!(service.attempt_with_cost(&store, 8).remaining, 2);
+    /// assert!(!service.attempt_with_cost(&store, 3).allowed);
+    /// assert!(service.attempt(&store).allowed);
+    /// ```
+    pub fn attempt_with_cost<S: ThrottleStore>(&self, store: &S, cost: u32) -> Decision {
+        self.try_attempt_with_cost(store, cost)
+            .unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`attempt_with_cost`](Self::attempt_with_cost), but returns store
+    /// errors instead of applying the failure policy.
+    pub fn try_attempt_with_cost<S: ThrottleStore>(
+        &self,
+        store: &S,
+        cost: u32,
+    ) -> Result<Decision, ThrottleError> {
+        self.run(store, Op::Attempt, cost)
+    }
+
+    /// Renders `decision` into rate-limit response headers advertising this
+    /// service's `max_attempts` per `period`, see [`rate_limit_headers`].
+    pub fn headers(
+        &self,
+        decision: &Decision,
+        dialect: HeaderDialect,
+    ) -> Vec<(&'static str, String)> {
+        rate_limit_headers(decision, self.max_attempts, self.period, dialect)
+    }
+
+    /// Waits until the limiter admits an attempt, records it, and waits out
+    /// its [`Decision::delay`] before returning.
+    ///
+    /// Meant for throttling your own outbound calls, e.g. to a third-party API,
+    /// rather than refusing them. Rejected attempts are retried as soon as their
+    /// `retry_after` has passed. Only returns a rejection when the key is
+    /// blocked outright, see [`until_ready_with`](Self::until_ready_with) for a
+    /// deadline.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use std::time::Duration;
+    /// use throttle_ro::store::MemoryStore;
+    /// use throttle_ro::{Algorithm, ThrottlesService};
+    ///
+    /// let store = MemoryStore::new();
+    /// let service = ThrottlesService::new("worker-1".to_string(), 20, Duration::from_secs(1), "jobs_")
+    ///     .with_algorithm(Algorithm::LeakyBucket { max_queue: 100 });
+    ///
+    /// if service.until_ready(&store).await.allowed {
+    ///     // Run the job at no more than 20 per second
+    /// }
+    /// # });
+    /// ```
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    pub async fn until_ready<S: AsyncThrottleStore>(&self, store: &S) -> Decision {
+        self.until_ready_with(store, Wait::new()).await
+    }
+
+    /// Like [`until_ready`](Self::until_ready), with jitter and a deadline.
+    ///
+    /// Returns the last rejection if the next retry would miss the deadline.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    pub async fn until_ready_with<S: AsyncThrottleStore>(&self, store: &S, wait: Wait) -> Decision {
+        loop {
+            let decision = self.attempt_async(store).await;
+            if decision.allowed {
+                tokio::time::sleep(decision.delay).await;
+                return decision;
+            }
+            match wait.backoff(&decision) {
+                Some(backoff) => tokio::time::sleep(backoff).await,
+                None => return decision,
+            }
+        }
+    }
+
+    /// Waits until the limiter admits an attempt, records it, and waits out
+    /// its [`Decision::delay`] before returning, putting the current thread to
+    /// sleep.
+    ///
+    /// Rejected attempts are retried as `wait` directs. This is the blocking
+    /// version of `until_ready_with`, available with the `tokio` feature.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use throttle_ro::{ThrottlesService, Wait};
+    /// use throttle_ro::store::MemoryStore;
+    ///
+    /// let store = MemoryStore::new();
+    /// let service = ThrottlesService::new("geocoder".to_string(), 50, Duration::from_secs(1), "outbound_");
+    ///
+    /// let decision = service.wait_until_ready(&store, Wait::new().timeout(Duration::from_secs(10)));
+    /// if decision.allowed {
+    ///     // Call the geocoding API
+    /// }
+    /// ```
+    pub fn wait_until_ready<S: ThrottleStore>(&self, store: &S, wait: Wait) -> Decision {
+        loop {
+            let decision = self.attempt(store);
+            if decision.allowed {
+                thread::sleep(decision.delay);
+                return decision;
+            }
+            match wait.backoff(&decision) {
+                Some(backoff) => thread::sleep(backoff),
+                None => return decision,
+            }
         }
     }
 
     /// Records an attempt (hit) from the IP.
     ///
     /// Increments the attempt count and resets the expiration time.
-    pub fn hit(&mut self, cache: &Cache) {
+    pub fn hit<S: ThrottleStore>(&mut self, store: &S) {
+        if let Err(error) = self.try_hit(store) {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`hit`](Self::hit), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_hit<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        self.try_hit_n(store, 1)
+    }
+
+    /// Records `cost` attempts at once, see [`attempt_with_cost`](Self::attempt_with_cost).
+    pub fn hit_n<S: ThrottleStore>(&self, store: &S, cost: u32) {
+        if let Err(error) = self.try_hit_n(store, cost) {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`hit_n`](Self::hit_n), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_hit_n<S: ThrottleStore>(&self, store: &S, cost: u32) -> Result<(), ThrottleError> {
+        if self.short_circuit().is_some() {
+            return Ok(());
+        }
+        match self.algorithm {
+            Algorithm::FixedWindow => {
+                store.increment(&self.key(), cost, self.period)?;
+            }
+            _ => {
+                let _ = self.run(store, Op::Hit, cost)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn run<S: ThrottleStore>(
+        &self,
+        store: &S,
+        op: Op,
+        cost: u32,
+    ) -> Result<Decision, ThrottleError> {
+        if let Some(decision) = self.short_circuit() {
+            return Ok(decision);
+        }
+
         let key = self.key();
-        let expire = self.get_expire(cache);
+        let now = clock::unix_millis(self.clock.now());
+        let (algorithm, limit, period) = (self.algorithm, self.max_attempts, self.period);
+        if let Some(result) = store.run_native(&key, algorithm, limit, period, cost, op) {
+            return result;
+        }
+        transition!(self, op, cost, now, |f| store.update(&key, f))
+    }
 
-        match self.get_value(cache) {
-            None => cache.set::<u32>(&key, 1, expire).unwrap(),
-            Some(v) => cache.set::<u32>(&key, v + 1, expire).unwrap(),
+    /// Handles a store error according to the failure policy.
+    fn fail(&self, error: ThrottleError) -> Decision {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = &self.failure_hook {
+            hook(&error);
+        }
+        let limit = self.max_attempts;
+        match self.failure_policy {
+            FailurePolicy::Panic => panic!("{error}"),
+            FailurePolicy::Open => Decision::allow(limit, limit, Duration::ZERO),
+            FailurePolicy::Closed => Decision::deny(limit, self.period, self.period),
+        }
+    }
+
+    /// Returns the decision of the access list, if it covers this key.
+    fn short_circuit(&self) -> Option<Decision> {
+        let limit = self.max_attempts;
+        match self.access.as_ref()?.get(self.ip?)? {
+            Access::Allow => Some(Decision::allow(limit, limit, Duration::ZERO)),
+            Access::Deny => Some(Decision::block(limit)),
         }
     }
 
     /// Clears the attempt count for the IP.
-    pub fn remove(&self, cache: &Cache) {
-        cache.remove(&self.key()).unwrap();
+    pub fn remove<S: ThrottleStore>(&self, store: &S) {
+        if let Err(error) = self.try_remove(store) {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`remove`](Self::remove), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_remove<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        store.remove(&self.key())
+    }
+
+    /// Like [`check`](Self::check), on an [`AsyncThrottleStore`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn check_async<S: AsyncThrottleStore>(&self, store: &S) -> Decision {
+        let result = self.try_check_async(store).await;
+        result.unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`check_async`](Self::check_async), but returns store errors instead of
+    /// applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_check_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<Decision, ThrottleError> {
+        self.run_async(store, Op::Check, 1).await
+    }
+
+    /// Like [`attempt`](Self::attempt), on an [`AsyncThrottleStore`].
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use std::time::Duration;
+    /// use throttle_ro::ThrottlesService;
+    /// use throttle_ro::store::MemoryStore;
+    ///
+    /// let store = MemoryStore::new();
+    /// let service = ThrottlesService::new("127.0.0.1".to_string(), 5, Duration::from_secs(60), "api_");
+    ///
+    /// if service.attempt_async(&store).await.allowed {
+    ///     // Process the request
+    /// }
+    /// # });
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn attempt_async<S: AsyncThrottleStore>(&self, store: &S) -> Decision {
+        let result = self.try_attempt_async(store).await;
+        result.unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`attempt_async`](Self::attempt_async), but returns store errors instead of
+    /// applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_attempt_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<Decision, ThrottleError> {
+        self.run_async(store, Op::Attempt, 1).await
+    }
+
+    /// Like [`attempt_with_cost`](Self::attempt_with_cost), on an [`AsyncThrottleStore`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn attempt_with_cost_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+        cost: u32,
+    ) -> Decision {
+        let result = self.try_attempt_with_cost_async(store, cost).await;
+        result.unwrap_or_else(|error| self.fail(error))
+    }
+
+    /// Like [`attempt_with_cost_async`](Self::attempt_with_cost_async), but returns
+    /// store errors instead of applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_attempt_with_cost_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+        cost: u32,
+    ) -> Result<Decision, ThrottleError> {
+        self.run_async(store, Op::Attempt, cost).await
+    }
+
+    /// Like [`hit`](Self::hit), on an [`AsyncThrottleStore`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn hit_async<S: AsyncThrottleStore>(&self, store: &S) {
+        if let Err(error) = self.try_hit_async(store).await {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`hit_async`](Self::hit_async), but returns store errors
+    /// instead of applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_hit_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<(), ThrottleError> {
+        self.try_hit_n_async(store, 1).await
+    }
+
+    /// Like [`hit_n`](Self::hit_n), on an [`AsyncThrottleStore`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn hit_n_async<S: AsyncThrottleStore>(&self, store: &S, cost: u32) {
+        if let Err(error) = self.try_hit_n_async(store, cost).await {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`hit_n_async`](Self::hit_n_async), but returns store errors
+    /// instead of applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_hit_n_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+        cost: u32,
+    ) -> Result<(), ThrottleError> {
+        if self.short_circuit().is_some() {
+            return Ok(());
+        }
+        match self.algorithm {
+            Algorithm::FixedWindow => {
+                store.increment(&self.key(), cost, self.period).await?;
+            }
+            _ => {
+                let _ = self.run_async(store, Op::Hit, cost).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`remove`](Self::remove), on an [`AsyncThrottleStore`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn remove_async<S: AsyncThrottleStore>(&self, store: &S) {
+        if let Err(error) = self.try_remove_async(store).await {
+            let _ = self.fail(error);
+        }
+    }
+
+    /// Like [`remove_async`](Self::remove_async), but returns store errors instead of
+    /// applying the failure policy.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn try_remove_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<(), ThrottleError> {
+        store.remove(&self.key()).await
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_async<S: AsyncThrottleStore>(
+        &self,
+        store: &S,
+        op: Op,
+        cost: u32,
+    ) -> Result<Decision, ThrottleError> {
+        if let Some(decision) = self.short_circuit() {
+            return Ok(decision);
+        }
+
+        let key = self.key();
+        let now = clock::unix_millis(self.clock.now());
+        let (algorithm, limit, period) = (self.algorithm, self.max_attempts, self.period);
+        let native = store
+            .run_native(&key, algorithm, limit, period, cost, op)
+            .await;
+        if let Some(result) = native {
+            return result;
+        }
+        transition!(self, op, cost, now, |f| store.update(&key, f).await)
     }
 }
@@ -0,0 +1,20 @@
+//! Striped per-key locks that make read-modify-write cycles on the cache atomic.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+const STRIPES: usize = 64;
+
+static LOCKS: [Mutex<()>; STRIPES] = [const { Mutex::new(()) }; STRIPES];
+
+/// Acquires the lock guarding `key`.
+///
+/// Keys are hashed onto a fixed set of stripes, so unrelated keys may
+/// occasionally share a lock but the lock table never grows.
+pub(crate) fn lock(key: &str) -> MutexGuard<'static, ()> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let index = (hasher.finish() % STRIPES as u64) as usize;
+    LOCKS[index].lock().unwrap_or_else(|e| e.into_inner())
+}
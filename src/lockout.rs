@@ -0,0 +1,290 @@
+use crate::clock::{self, Clock, saturating_millis};
+use crate::key::AddressString;
+use crate::{
+    AccessList, Decision, FailurePolicy, ThrottleError, ThrottleKey, ThrottleStore,
+    ThrottlesService,
+};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Failures in the current window, window start, escalation level and
+/// lockout end, all timestamps in unix milliseconds.
+type LockoutState = (u32, u64, u32, u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Check,
+    Failure,
+    Success,
+}
+
+/// Locks a key out for escalating periods after repeated failures, for login
+/// and OTP verification.
+///
+/// Every `max_failures` failures within `window` lock the key for the next
+/// penalty: 1 minute, then 5, then 30 by default, staying at the last one.
+/// The escalation level is remembered until no failure has been recorded for
+/// the decay period (a day by default), so an attacker who waits out a
+/// lockout resumes at the next level instead of starting over.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::Lockout;
+/// use throttle_ro::store::MemoryStore;
+///
+/// let store = MemoryStore::new();
+/// let lockout = Lockout::for_key(&("alice", "203.0.113.7"), 3, Duration::from_secs(900), "login_");
+///
+/// for _ in 0..3 {
+///     let _ = lockout.record_failure(&store);
+/// }
+/// let decision = lockout.check(&store);
+/// assert!(!decision.allowed);
+/// assert_eq!(decision.retry_after, Some(Duration::from_secs(60)));
+/// ```
+#[derive(Clone)]
+pub struct Lockout {
+    service: ThrottlesService,
+    penalties: Vec<Duration>,
+    decay: Duration,
+}
+
+impl Lockout {
+    /// Creates a new `Lockout` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address to track
+    /// * `max_failures` - Failures allowed in `window` before locking the key
+    /// * `window` - Duration failures are counted over
+    /// * `prefix` - Prefix for cache keys to avoid collisions
+    pub fn new(ip: String, max_failures: u32, window: Duration, prefix: &str) -> Self {
+        Self::for_key(&AddressString(&ip), max_failures, window, prefix)
+    }
+
+    /// Creates a `Lockout` keyed by anything implementing [`ThrottleKey`],
+    /// such as a username.
+    pub fn for_key<K: ThrottleKey + ?Sized>(
+        key: &K,
+        max_failures: u32,
+        window: Duration,
+        prefix: &str,
+    ) -> Self {
+        Self {
+            service: ThrottlesService::for_key(key, max_failures, window, prefix),
+            penalties: vec![
+                Duration::from_secs(60),
+                Duration::from_secs(5 * 60),
+                Duration::from_secs(30 * 60),
+            ],
+            decay: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Creates a `Lockout` for a parsed IP address, aggregated like
+    /// [`ThrottlesService::for_ip`].
+    pub fn for_ip(ip: IpAddr, max_failures: u32, window: Duration, prefix: &str) -> Self {
+        Self::for_key(&ip, max_failures, window, prefix)
+    }
+
+    /// Sets the lockout durations of each escalation level, the last one
+    /// applying to every lockout after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `penalties` is empty.
+    pub fn with_penalties(mut self, penalties: &[Duration]) -> Self {
+        assert!(!penalties.is_empty(), "Lockout needs at least one penalty");
+        self.penalties = penalties.to_vec();
+        self
+    }
+
+    /// Sets how long the escalation level is remembered after the last failure.
+    pub fn with_decay(mut self, decay: Duration) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Sets the clock lockouts are measured with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.service = self.service.with_clock(clock);
+        self
+    }
+
+    /// Consults `access` before recording or checking any failure.
+    ///
+    /// Only address keys, from [`new`](Self::new) and [`for_ip`](Self::for_ip),
+    /// are matched. A username passed to [`for_key`](Self::for_key) is always
+    /// counted, even when it looks like an address.
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.service = self.service.with_access_list(access);
+        self
+    }
+
+    /// Selects what to answer when the store fails.
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.service = self.service.with_failure_policy(policy);
+        self
+    }
+
+    /// Calls `hook` with every store error the [`FailurePolicy`] handles.
+    pub fn with_failure_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        self.service = self.service.with_failure_hook(hook);
+        self
+    }
+
+    /// Returns how many store errors the failure policy has handled.
+    pub fn failures(&self) -> u64 {
+        self.service.failures()
+    }
+
+    /// Returns the cache key the lockout state is stored under.
+    pub fn key(&self) -> String {
+        self.service.key()
+    }
+
+    /// Reports whether the key may try again, without recording anything.
+    ///
+    /// While locked out, `retry_after` is the time left on the lockout.
+    pub fn check<S: ThrottleStore>(&self, store: &S) -> Decision {
+        let result = self.try_check(store);
+        result.unwrap_or_else(|error| self.service.fail(error))
+    }
+
+    /// Like [`check`](Self::check), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_check<S: ThrottleStore>(&self, store: &S) -> Result<Decision, ThrottleError> {
+        self.run(store, Event::Check)
+    }
+
+    /// Records a failed login or verification.
+    ///
+    /// Returns a rejection when the key is locked out, including when this
+    /// failure is the one that locked it. Failures recorded during a lockout
+    /// are not counted.
+    pub fn record_failure<S: ThrottleStore>(&self, store: &S) -> Decision {
+        let result = self.try_record_failure(store);
+        result.unwrap_or_else(|error| self.service.fail(error))
+    }
+
+    /// Like [`record_failure`](Self::record_failure), but returns store errors
+    /// instead of applying the failure policy.
+    pub fn try_record_failure<S: ThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<Decision, ThrottleError> {
+        self.run(store, Event::Failure)
+    }
+
+    /// Records a successful login, clearing the failures counted so far.
+    ///
+    /// The escalation level is kept until it decays, so alternating between a
+    /// known password and guesses does not reset the penalties. A success does
+    /// not postpone the decay, which only counts from the last failure. Use
+    /// [`reset`](Self::reset) to forget the key entirely.
+    pub fn record_success<S: ThrottleStore>(&self, store: &S) {
+        if let Err(error) = self.try_record_success(store) {
+            let _ = self.service.fail(error);
+        }
+    }
+
+    /// Like [`record_success`](Self::record_success), but returns store errors
+    /// instead of applying the failure policy.
+    pub fn try_record_success<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        self.run(store, Event::Success).map(|_| ())
+    }
+
+    /// Clears the failures, lockout and escalation level of the key.
+    pub fn reset<S: ThrottleStore>(&self, store: &S) {
+        self.service.remove(store);
+    }
+
+    /// Like [`reset`](Self::reset), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_reset<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        self.service.try_remove(store)
+    }
+
+    fn run<S: ThrottleStore>(&self, store: &S, event: Event) -> Result<Decision, ThrottleError> {
+        if let Some(decision) = self.service.short_circuit() {
+            return Ok(decision);
+        }
+
+        let now = clock::unix_millis(self.service.clock.now());
+        store.update(&self.key(), |state, ttl| {
+            self.transition(state, ttl, now, event)
+        })
+    }
+
+    fn transition(
+        &self,
+        state: Option<LockoutState>,
+        ttl: Option<Duration>,
+        now: u64,
+        event: Event,
+    ) -> (Option<(LockoutState, Duration)>, Decision) {
+        let limit = self.service.max_attempts;
+        let window = saturating_millis(self.service.period);
+        let stored = state.is_some();
+        let (mut failures, mut window_start, mut level, mut locked_until) =
+            state.unwrap_or((0, now, 0, 0));
+
+        if now >= window_start.saturating_add(window) {
+            failures = 0;
+            window_start = now;
+        }
+
+        let decision = if locked_until > now {
+            let left = Duration::from_millis(locked_until - now);
+            if event == Event::Success {
+                failures = 0;
+            }
+            Decision::deny(limit, left, left)
+        } else {
+            match event {
+                Event::Check => {}
+                Event::Failure => failures = failures.saturating_add(1),
+                Event::Success => failures = 0,
+            }
+            if event == Event::Failure && failures >= limit {
+                let penalty = self.penalties[(level as usize).min(self.penalties.len() - 1)];
+                locked_until = now.saturating_add(saturating_millis(penalty));
+                level = level.saturating_add(1);
+                failures = 0;
+                window_start = now;
+                Decision::deny(limit, penalty, penalty)
+            } else {
+                let reset = window_start.saturating_add(window).saturating_sub(now);
+                Decision::allow(
+                    limit,
+                    limit.saturating_sub(failures),
+                    Duration::from_millis(reset),
+                )
+            }
+        };
+
+        let ttl = match event {
+            Event::Check => return (None, decision),
+            // A success only clears the failures, so the state expires as before.
+            Event::Success => match ttl {
+                Some(ttl) if stored => ttl,
+                _ => return (None, decision),
+            },
+            Event::Failure => {
+                let window_left = window_start.saturating_add(window).saturating_sub(now);
+                self.decay
+                    .max(Duration::from_millis(locked_until.saturating_sub(now)))
+                    .max(Duration::from_millis(window_left))
+            }
+        };
+        (
+            Some(((failures, window_start, level, locked_until), ttl)),
+            decision,
+        )
+    }
+}
@@ -0,0 +1,294 @@
+use crate::clock::Clock;
+use crate::failure::FailureHook;
+use crate::{
+    AccessList, Algorithm, Decision, FailurePolicy, KeyBuilder, ThrottleError, ThrottleStore,
+    ThrottlesService,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One of the failure counters kept by a [`LoginGuard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoginCounter {
+    /// Failures against one account from any address, which catches guessing
+    /// spread across many IPs.
+    Username,
+    /// Failures from one address against any account, which catches one IP
+    /// trying many accounts.
+    Ip,
+    /// Failures against one account from one address.
+    UsernameIp,
+}
+
+impl LoginCounter {
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Username => "user_",
+            Self::Ip => "ip_",
+            Self::UsernameIp => "user_ip_",
+        }
+    }
+}
+
+/// The outcome of checking every counter of a [`LoginGuard`].
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginDecision {
+    /// Whether every counter admits another login attempt.
+    pub allowed: bool,
+    /// The counter that caused the rejection, if any.
+    ///
+    /// When several counters are exhausted, this is the one that is blocked the longest.
+    pub rejected_by: Option<LoginCounter>,
+    /// The decision of each configured counter.
+    pub counters: Vec<(LoginCounter, Decision)>,
+}
+
+impl LoginDecision {
+    fn new(counters: Vec<(LoginCounter, Decision)>) -> Self {
+        let rejected_by = counters
+            .iter()
+            .filter(|(_, decision)| !decision.allowed)
+            .max_by_key(|(_, decision)| decision.retry_after)
+            .map(|&(counter, _)| counter);
+
+        Self {
+            allowed: rejected_by.is_none(),
+            rejected_by,
+            counters,
+        }
+    }
+
+    /// Returns the decision of `counter`, if it is configured.
+    pub fn get(&self, counter: LoginCounter) -> Option<&Decision> {
+        self.counters
+            .iter()
+            .find(|(c, _)| *c == counter)
+            .map(|(_, decision)| decision)
+    }
+}
+
+/// Throttles failed logins per account, per address and per account and
+/// address at once.
+///
+/// Attackers spread guesses against one account over many IPs, or try many
+/// accounts from one IP; each [`LoginCounter`] catches one of those, with its
+/// own limit. Counters without a limit are not tracked.
+///
+/// Check the guard before verifying the credentials, then record the outcome.
+/// By default a successful login clears the [`Username`](LoginCounter::Username)
+/// and [`UsernameIp`](LoginCounter::UsernameIp) counters but not the
+/// [`Ip`](LoginCounter::Ip) one, so an attacker owning one valid account
+/// cannot reset the budget of an address trying many others.
+///
+/// Usernames are used as given, so normalize their case first if your
+/// accounts are case-insensitive.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::IpAddr;
+/// use std::time::Duration;
+/// use throttle_ro::{LoginCounter, LoginGuard};
+/// use throttle_ro::store::MemoryStore;
+///
+/// let store = MemoryStore::new();
+/// let guard = LoginGuard::new("login_")
+///     .with_limit(LoginCounter::Username, 20, Duration::from_secs(3600))
+///     .with_limit(LoginCounter::Ip, 100, Duration::from_secs(3600))
+///     .with_limit(LoginCounter::UsernameIp, 5, Duration::from_secs(900));
+///
+/// let ip: IpAddr = "203.0.113.7".parse().unwrap();
+/// if guard.check(&store, "alice", ip).allowed {
+///     # let password_ok = false;
+///     if password_ok {
+///         guard.record_success(&store, "alice", ip);
+///     } else {
+///         guard.record_failure(&store, "alice", ip);
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct LoginGuard {
+    template: ThrottlesService,
+    counters: Vec<(LoginCounter, ThrottlesService)>,
+    clear_on_success: Vec<LoginCounter>,
+}
+
+impl LoginGuard {
+    /// Creates a `LoginGuard` without any counters.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Prefix for cache keys to avoid collisions
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            template: ThrottlesService::for_key("", 0, Duration::ZERO, prefix),
+            counters: Vec::new(),
+            clear_on_success: vec![LoginCounter::Username, LoginCounter::UsernameIp],
+        }
+    }
+
+    /// Allows `max_failures` failures per `period` on `counter`, replacing
+    /// any limit it already had.
+    pub fn with_limit(
+        mut self,
+        counter: LoginCounter,
+        max_failures: u32,
+        period: Duration,
+    ) -> Self {
+        let mut prefix = KeyBuilder::new();
+        prefix.part(&self.template.prefix).part(counter.prefix());
+        let service = ThrottlesService {
+            max_attempts: max_failures,
+            period,
+            prefix: prefix.build(),
+            ..self.template.clone()
+        };
+        self.counters.retain(|(c, _)| *c != counter);
+        self.counters.push((counter, service));
+        self
+    }
+
+    /// Selects which counters a successful login clears.
+    pub fn with_clear_on_success(mut self, counters: &[LoginCounter]) -> Self {
+        self.clear_on_success = counters.to_vec();
+        self
+    }
+
+    /// Selects the algorithm used by every counter.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.map_services(|service| service.with_algorithm(algorithm));
+        self
+    }
+
+    /// Sets the clock every counter reads the current time from.
+    pub fn with_clock<C: Clock + Clone + 'static>(mut self, clock: C) -> Self {
+        self.map_services(|service| service.with_clock(clock.clone()));
+        self
+    }
+
+    /// Consults `access` before counting any failure from an address.
+    ///
+    /// The [`Username`](LoginCounter::Username) counter has no address and is
+    /// always consulted, even for usernames that look like an address.
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.map_services(|service| service.with_access_list(access.clone()));
+        self
+    }
+
+    /// Selects what every counter answers when the store fails.
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.map_services(|service| service.with_failure_policy(policy));
+        self
+    }
+
+    /// Calls `hook` with every store error the [`FailurePolicy`] handles.
+    pub fn with_failure_hook<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        let hook: FailureHook = Arc::new(hook);
+        self.map_services(|service| service.with_shared_failure_hook(hook.clone()));
+        self
+    }
+
+    /// Returns how many store errors the failure policy has handled.
+    pub fn failures(&self) -> u64 {
+        self.template.failures()
+    }
+
+    /// Reports whether `username` may try to log in from `ip`, without
+    /// recording anything.
+    pub fn check<S: ThrottleStore>(&self, store: &S, username: &str, ip: IpAddr) -> LoginDecision {
+        let counters = self.services(username, ip);
+        let counters = counters.map(|(counter, service)| (counter, service.check(store)));
+        LoginDecision::new(counters.collect())
+    }
+
+    /// Like [`check`](Self::check), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_check<S: ThrottleStore>(
+        &self,
+        store: &S,
+        username: &str,
+        ip: IpAddr,
+    ) -> Result<LoginDecision, ThrottleError> {
+        let counters = self.services(username, ip);
+        let counters = counters
+            .map(|(counter, service)| service.try_check(store).map(|decision| (counter, decision)));
+        Ok(LoginDecision::new(counters.collect::<Result<_, _>>()?))
+    }
+
+    /// Records a failed login of `username` from `ip` in every counter.
+    pub fn record_failure<S: ThrottleStore>(&self, store: &S, username: &str, ip: IpAddr) {
+        for (_, mut service) in self.services(username, ip) {
+            service.hit(store);
+        }
+    }
+
+    /// Like [`record_failure`](Self::record_failure), but returns store errors
+    /// instead of applying the failure policy.
+    pub fn try_record_failure<S: ThrottleStore>(
+        &self,
+        store: &S,
+        username: &str,
+        ip: IpAddr,
+    ) -> Result<(), ThrottleError> {
+        self.services(username, ip)
+            .try_for_each(|(_, service)| service.try_hit(store))
+    }
+
+    /// Records a successful login of `username` from `ip`, clearing the
+    /// counters selected with [`with_clear_on_success`](Self::with_clear_on_success).
+    pub fn record_success<S: ThrottleStore>(&self, store: &S, username: &str, ip: IpAddr) {
+        for (_, service) in self.cleared_on_success(username, ip) {
+            service.remove(store);
+        }
+    }
+
+    /// Like [`record_success`](Self::record_success), but returns store errors
+    /// instead of applying the failure policy.
+    pub fn try_record_success<S: ThrottleStore>(
+        &self,
+        store: &S,
+        username: &str,
+        ip: IpAddr,
+    ) -> Result<(), ThrottleError> {
+        self.cleared_on_success(username, ip)
+            .try_for_each(|(_, service)| service.try_remove(store))
+    }
+
+    /// Returns the configured counters, keyed for `username` and `ip`.
+    fn services<'a>(
+        &'a self,
+        username: &'a str,
+        ip: IpAddr,
+    ) -> impl Iterator<Item = (LoginCounter, ThrottlesService)> + 'a {
+        self.counters.iter().map(move |(counter, service)| {
+            let service = match counter {
+                LoginCounter::Username => service.rekey(username),
+                LoginCounter::Ip => service.rekey(&ip),
+                LoginCounter::UsernameIp => service.rekey(&(username, ip)),
+            };
+            (*counter, service)
+        })
+    }
+
+    fn cleared_on_success<'a>(
+        &'a self,
+        username: &'a str,
+        ip: IpAddr,
+    ) -> impl Iterator<Item = (LoginCounter, ThrottlesService)> + 'a {
+        self.services(username, ip)
+            .filter(|(counter, _)| self.clear_on_success.contains(counter))
+    }
+
+    fn map_services(&mut self, f: impl Fn(ThrottlesService) -> ThrottlesService) {
+        self.template = f(self.template.clone());
+        for (_, service) in &mut self.counters {
+            *service = f(service.clone());
+        }
+    }
+}
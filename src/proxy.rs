@@ -0,0 +1,153 @@
+use crate::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+/// The forwarding header a [`TrustedProxies`] reads the client address from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    /// `Forwarded`, from RFC 7239.
+    Forwarded,
+    /// `X-Forwarded-For`.
+    #[default]
+    XForwardedFor,
+    /// `X-Real-IP`, holding a single address.
+    XRealIp,
+}
+
+impl ForwardedHeader {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Forwarded => "forwarded",
+            Self::XForwardedFor => "x-forwarded-for",
+            Self::XRealIp => "x-real-ip",
+        }
+    }
+}
+
+/// Resolves the real client address of requests relayed by trusted proxies.
+///
+/// Behind a load balancer every connection comes from the balancer, so the
+/// client address has to be taken from a forwarding header instead. Those
+/// headers can be forged by anyone, so they are only read when the peer is a
+/// trusted proxy, and only the hops appended by trusted proxies are believed:
+/// the chain is walked from the right and the first untrusted address is the
+/// client.
+///
+/// Only the header your proxies write is read, `X-Forwarded-For` unless
+/// selected otherwise with [`with_header`](Self::with_header). Any other
+/// forwarding header comes straight from the client and is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::IpAddr;
+/// use std::time::Duration;
+/// use throttle_ro::{ThrottlesService, TrustedProxies};
+///
+/// let proxies = TrustedProxies::new(["10.0.0.0/8".parse().unwrap()]);
+///
+/// let peer: IpAddr = "10.0.0.5".parse().unwrap();
+/// let headers = [("X-Forwarded-For", "198.51.100.1, 203.0.113.9, 10.0.0.7")];
+/// let client = proxies.client_ip(peer, headers);
+/// assert_eq!(client, "203.0.113.9".parse::<IpAddr>().unwrap());
+///
+/// let service = ThrottlesService::for_ip(client, 5, Duration::from_secs(60), "api_");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+    header: ForwardedHeader,
+}
+
+impl TrustedProxies {
+    /// Trusts proxies whose address falls into any of `networks`.
+    pub fn new(networks: impl IntoIterator<Item = IpNet>) -> Self {
+        Self {
+            networks: networks.into_iter().collect(),
+            header: ForwardedHeader::default(),
+        }
+    }
+
+    /// Reads the client address from `header`.
+    ///
+    /// Defaults to [`ForwardedHeader::XForwardedFor`].
+    pub fn with_header(mut self, header: ForwardedHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Returns whether `ip` is a trusted proxy.
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|net| net.contains(ip))
+    }
+
+    /// Returns the client address of a request received from `peer`.
+    ///
+    /// `headers` are `(name, value)` pairs; names are matched case-insensitively
+    /// and repeated headers are read in order. Returns `peer` when it is not a
+    /// trusted proxy or the configured header is absent. If a trusted hop
+    /// forwarded an unknown or obfuscated address, the address of that hop is
+    /// returned.
+    pub fn client_ip<'a>(
+        &self,
+        peer: IpAddr,
+        headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let mut hops = Vec::new();
+        for (name, value) in headers {
+            if !name.eq_ignore_ascii_case(self.header.name()) {
+                continue;
+            }
+            match self.header {
+                ForwardedHeader::Forwarded => {
+                    hops.extend(value.split(',').map(forwarded_for_param));
+                }
+                ForwardedHeader::XForwardedFor => hops.extend(value.split(',').map(parse_node)),
+                // Only the last value counts, as proxies overwrite the header.
+                ForwardedHeader::XRealIp => hops = vec![parse_node(value)],
+            }
+        }
+        if hops.is_empty() {
+            return peer;
+        }
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            match hop {
+                Some(ip) => client = ip,
+                None => return client,
+            }
+            if !self.is_trusted(client) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Extracts the `for=` node of one `Forwarded` element.
+fn forwarded_for_param(element: &str) -> Option<IpAddr> {
+    element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("for")
+            .then(|| parse_node(value))?
+    })
+}
+
+/// Parses a forwarded node: an address, optionally quoted, bracketed or with a port.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip().to_canonical());
+    }
+    let ip = node.strip_prefix('[')?.strip_suffix(']')?;
+    ip.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
@@ -0,0 +1,128 @@
+use super::{Op, StoreError, StoreValue, ThrottleStore};
+use crate::{Algorithm, Decision};
+use std::future::Future;
+use std::time::Duration;
+
+/// The non-blocking counterpart of [`ThrottleStore`], used by the `_async`
+/// methods of [`ThrottlesService`](crate::ThrottlesService).
+///
+/// Implement it for stores backed by an async client, so waiting on the
+/// network never blocks an executor thread. Every [`ThrottleStore`] implements
+/// it too by running its operations inline, which suits in-memory stores like
+/// [`MemoryStore`](super::MemoryStore) but not network stores: through this
+/// impl, `RedisStore` still blocks the executor thread for every round trip.
+///
+/// The same atomicity requirements as for [`ThrottleStore`] apply.
+pub trait AsyncThrottleStore: Send + Sync {
+    /// Returns the value stored under `key`, unless it is missing or expired.
+    fn get<V: StoreValue>(
+        &self,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<V>, StoreError>> + Send;
+
+    /// Stores `value` under `key`, expiring after `ttl`.
+    fn set<V: StoreValue>(
+        &self,
+        key: &str,
+        value: V,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<(), StoreError>> + Send;
+
+    /// Returns the time left before `key` expires, unless it is missing or expired.
+    fn ttl(&self, key: &str) -> impl Future<Output = Result<Option<Duration>, StoreError>> + Send;
+
+    /// Deletes `key`.
+    fn remove(&self, key: &str) -> impl Future<Output = Result<(), StoreError>> + Send;
+
+    /// Atomically reads `key`, passes its value and remaining TTL to `f`, and
+    /// stores the value `f` returns (if any) before handing back its result.
+    ///
+    /// See [`ThrottleStore::update`].
+    fn update<V, R, F>(
+        &self,
+        key: &str,
+        f: F,
+    ) -> impl Future<Output = Result<R, StoreError>> + Send
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R) + Send;
+
+    /// Atomically adds `by` to the counter under `key` and returns the new value.
+    ///
+    /// See [`ThrottleStore::increment`].
+    fn increment(
+        &self,
+        key: &str,
+        by: u32,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<u32, StoreError>> + Send {
+        self.update(key, move |count: Option<u32>, remaining| {
+            let count = count.unwrap_or(0).saturating_add(by);
+            let ttl = remaining.unwrap_or(ttl);
+            (Some((count, ttl)), count)
+        })
+    }
+
+    /// Runs `algorithm` for `key` as a single server-side operation.
+    ///
+    /// See [`ThrottleStore::run_native`].
+    fn run_native(
+        &self,
+        key: &str,
+        algorithm: Algorithm,
+        limit: u32,
+        period: Duration,
+        cost: u32,
+        op: Op,
+    ) -> impl Future<Output = Option<Result<Decision, StoreError>>> + Send {
+        let _ = (key, algorithm, limit, period, cost, op);
+        async { None }
+    }
+}
+
+impl<S: ThrottleStore + Send + Sync> AsyncThrottleStore for S {
+    async fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        ThrottleStore::get(self, key)
+    }
+
+    async fn set<V: StoreValue>(
+        &self,
+        key: &str,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), StoreError> {
+        ThrottleStore::set(self, key, value, ttl)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        ThrottleStore::ttl(self, key)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), StoreError> {
+        ThrottleStore::remove(self, key)
+    }
+
+    async fn update<V, R, F>(&self, key: &str, f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R) + Send,
+    {
+        ThrottleStore::update(self, key, f)
+    }
+
+    async fn increment(&self, key: &str, by: u32, ttl: Duration) -> Result<u32, StoreError> {
+        ThrottleStore::increment(self, key, by, ttl)
+    }
+
+    async fn run_native(
+        &self,
+        key: &str,
+        algorithm: Algorithm,
+        limit: u32,
+        period: Duration,
+        cost: u32,
+        op: Op,
+    ) -> Option<Result<Decision, StoreError>> {
+        ThrottleStore::run_native(self, key, algorithm, limit, period, cost, op)
+    }
+}
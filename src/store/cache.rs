@@ -0,0 +1,41 @@
+use super::{StoreError, StoreValue, ThrottleStore};
+use crate::ThrottleError;
+use crate::lock;
+use cache_ro::Cache;
+use std::time::Duration;
+
+/// Stores throttling state in the global [`cache_ro`] instance.
+///
+/// `cache_ro` has no atomic operations of its own, so read-modify-write cycles
+/// are serialized with a striped per-key lock within this process.
+#[cfg_attr(docsrs, doc(cfg(feature = "cache-ro")))]
+impl ThrottleStore for Cache {
+    fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        Ok(Cache::get::<V>(self, key))
+    }
+
+    fn set<V: StoreValue>(&self, key: &str, value: V, ttl: Duration) -> Result<(), StoreError> {
+        Cache::set::<V>(self, key, value, ttl).map_err(ThrottleError::from_cache)
+    }
+
+    fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        Ok(self.expire(key))
+    }
+
+    fn remove(&self, key: &str) -> Result<(), StoreError> {
+        Cache::remove(self, key).map_err(ThrottleError::from_cache)
+    }
+
+    fn update<V, R, F>(&self, key: &str, mut f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R),
+    {
+        let _guard = lock::lock(key);
+        let (state, result) = f(Cache::get::<V>(self, key), self.expire(key));
+        if let Some((value, ttl)) = state {
+            Cache::set::<V>(self, key, value, ttl).map_err(ThrottleError::from_cache)?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,265 @@
+use super::{Op, StoreError, StoreValue, ThrottleStore};
+use crate::algorithm;
+use crate::clock::{self, Clock, SystemClock, saturating_millis};
+use crate::{Algorithm, Decision};
+use dashmap::DashMap;
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Number of inserts between two sweeps of expired entries.
+const SWEEP_INTERVAL: usize = 4096;
+
+enum Value {
+    Counter(AtomicU32),
+    Other(Box<dyn Any + Send + Sync>),
+}
+
+impl Value {
+    fn new<V: StoreValue>(value: V) -> Self {
+        let boxed: Box<dyn Any + Send + Sync> = Box::new(value);
+        match boxed.downcast::<u32>() {
+            Ok(count) => Value::Counter(AtomicU32::new(*count)),
+            Err(other) => Value::Other(other),
+        }
+    }
+
+    fn get<V: StoreValue>(&self) -> Option<V> {
+        match self {
+            Value::Counter(count) => {
+                let count = count.load(Ordering::Acquire);
+                (&count as &dyn Any).downcast_ref::<V>().cloned()
+            }
+            Value::Other(value) => value.downcast_ref::<V>().cloned(),
+        }
+    }
+}
+
+struct Entry {
+    value: Value,
+    expires_at: u64,
+}
+
+impl Entry {
+    fn new(value: Value, ttl: Duration, now: u64) -> Self {
+        Self {
+            value,
+            expires_at: now.saturating_add(saturating_millis(ttl)),
+        }
+    }
+
+    fn ttl(&self, now: u64) -> Option<Duration> {
+        (self.expires_at > now).then(|| Duration::from_millis(self.expires_at - now))
+    }
+}
+
+/// An in-process store for single-instance deployments.
+///
+/// Entries live in a sharded map, so operations on different keys rarely
+/// contend. Fixed-window counters are checked and bumped with atomic
+/// instructions under a shared shard lock, so concurrent attempts on a live
+/// window never wait for each other; other algorithms take the shard's write
+/// lock for each update. Expired entries are dropped lazily when they are next
+/// touched, and swept in bulk every few thousand inserts.
+///
+/// Expiration is measured with a [`Clock`], the system clock by default.
+///
+/// Cloning a `MemoryStore` yields another handle to the same entries.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::ThrottlesService;
+/// use throttle_ro::store::MemoryStore;
+///
+/// let store = MemoryStore::new();
+/// let service = ThrottlesService::new("127.0.0.1".to_string(), 5, Duration::from_secs(60), "api_");
+///
+/// assert!(service.attempt(&store).allowed);
+/// ```
+#[derive(Clone)]
+pub struct MemoryStore {
+    entries: Arc<DashMap<String, Entry>>,
+    inserts: Arc<AtomicUsize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+
+    /// Creates an empty store that measures expiration with `clock`.
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        Self {
+            entries: Arc::default(),
+            inserts: Arc::default(),
+            clock: Arc::new(clock),
+        }
+    }
+
+    /// Returns the number of entries, including expired ones not yet evicted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the store holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Evicts every expired entry.
+    pub fn purge_expired(&self) {
+        let now = self.now();
+        self.entries.retain(|_, entry| entry.expires_at > now);
+    }
+
+    fn now(&self) -> u64 {
+        clock::unix_millis(self.clock.now())
+    }
+
+    fn insert(&self, key: &str, value: Value, ttl: Duration, now: u64) {
+        self.entries
+            .insert(key.to_string(), Entry::new(value, ttl, now));
+        self.inserted();
+    }
+
+    fn inserted(&self) {
+        if self.inserts.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL == SWEEP_INTERVAL - 1 {
+            self.purge_expired();
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThrottleStore for MemoryStore {
+    fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        let now = self.now();
+        match self.entries.get(key) {
+            Some(entry) if entry.expires_at > now => return Ok(entry.value.get()),
+            Some(_) => {}
+            None => return Ok(None),
+        }
+        self.entries
+            .remove_if(key, |_, entry| entry.expires_at <= now);
+        Ok(None)
+    }
+
+    fn set<V: StoreValue>(&self, key: &str, value: V, ttl: Duration) -> Result<(), StoreError> {
+        self.insert(key, Value::new(value), ttl, self.now());
+        Ok(())
+    }
+
+    fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        let now = self.now();
+        Ok(self.entries.get(key).and_then(|entry| entry.ttl(now)))
+    }
+
+    fn remove(&self, key: &str) -> Result<(), StoreError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn update<V, R, F>(&self, key: &str, mut f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R),
+    {
+        let now = self.now();
+        let mut entry = self.entries.entry(key.to_string());
+        let (value, ttl) = match &entry {
+            dashmap::Entry::Occupied(occupied) if occupied.get().expires_at > now => {
+                (occupied.get().value.get(), occupied.get().ttl(now))
+            }
+            _ => (None, None),
+        };
+
+        let (state, result) = f(value, ttl);
+        match state {
+            Some((value, ttl)) => {
+                let value = Entry::new(Value::new(value), ttl, now);
+                match entry {
+                    dashmap::Entry::Occupied(ref mut occupied) => {
+                        occupied.insert(value);
+                    }
+                    dashmap::Entry::Vacant(vacant) => {
+                        vacant.insert(value);
+                        self.inserted();
+                    }
+                }
+            }
+            None => {
+                if let dashmap::Entry::Occupied(occupied) = entry
+                    && occupied.get().expires_at <= now
+                {
+                    occupied.remove();
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn increment(&self, key: &str, by: u32, ttl: Duration) -> Result<u32, StoreError> {
+        let now = self.now();
+        if let Some(entry) = self.entries.get(key)
+            && let Value::Counter(count) = &entry.value
+            && entry.expires_at > now
+        {
+            let previous = count
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                    Some(count.saturating_add(by))
+                })
+                .unwrap_or_default();
+            return Ok(previous.saturating_add(by));
+        }
+
+        self.update(key, |count: Option<u32>, remaining| {
+            let count = count.unwrap_or(0).saturating_add(by);
+            (Some((count, remaining.unwrap_or(ttl))), count)
+        })
+    }
+
+    fn run_native(
+        &self,
+        key: &str,
+        algorithm: Algorithm,
+        limit: u32,
+        period: Duration,
+        cost: u32,
+        op: Op,
+    ) -> Option<Result<Decision, StoreError>> {
+        if !matches!(algorithm, Algorithm::FixedWindow) {
+            return None;
+        }
+
+        // Only live counters take the fast path; creating or renewing a window
+        // goes through `update`.
+        let now = self.now();
+        let entry = self.entries.get(key)?;
+        let Value::Counter(count) = &entry.value else {
+            return None;
+        };
+        let ttl = entry.ttl(now)?;
+
+        let previous = match op {
+            Op::Check => count.load(Ordering::Acquire),
+            Op::Attempt | Op::Hit => count
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                    let fits = count.saturating_add(cost) <= limit;
+                    (fits || op == Op::Hit).then(|| count.saturating_add(cost))
+                })
+                .unwrap_or_else(|count| count),
+        };
+        let transition =
+            algorithm::fixed_window(Some(previous), Some(ttl), limit, period, cost, op);
+        Some(Ok(transition.1))
+    }
+}
@@ -0,0 +1,101 @@
+//! Storage backends for throttling state.
+//!
+//! Every [`ThrottlesService`](crate::ThrottlesService) operation takes a
+//! [`ThrottleStore`], so the limiter can run on top of any key-value store that
+//! supports per-key TTLs and an atomic read-modify-write cycle.
+
+#[cfg(feature = "async")]
+mod async_store;
+#[cfg(feature = "cache-ro")]
+mod cache;
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+use crate::{Algorithm, Decision, ThrottleError};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStore;
+pub use crate::algorithm::Op;
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub use async_store::AsyncThrottleStore;
+pub use memory::MemoryStore;
+
+/// The error type returned by storage backends.
+///
+/// An alias of [`ThrottleError`]; custom stores can convert their own errors
+/// into it with `?` when they are boxed, or pick a variant explicitly.
+pub type StoreError = ThrottleError;
+
+/// A value that can be kept in a [`ThrottleStore`].
+///
+/// Implemented for every type that is serializable, cloneable and thread-safe,
+/// so backends are free to either serialize values or keep them as they are.
+pub trait StoreValue: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {}
+
+impl<T> StoreValue for T where T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {}
+
+/// A key-value store with per-key expiration that throttling state is kept in.
+///
+/// Implementations must make [`update`](Self::update) and
+/// [`increment`](Self::increment) atomic with respect to each other for the
+/// same key; this is what keeps limits exact under concurrent requests.
+pub trait ThrottleStore {
+    /// Returns the value stored under `key`, unless it is missing or expired.
+    fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError>;
+
+    /// Stores `value` under `key`, expiring after `ttl`.
+    fn set<V: StoreValue>(&self, key: &str, value: V, ttl: Duration) -> Result<(), StoreError>;
+
+    /// Returns the time left before `key` expires, unless it is missing or expired.
+    fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError>;
+
+    /// Deletes `key`.
+    fn remove(&self, key: &str) -> Result<(), StoreError>;
+
+    /// Atomically reads `key`, passes its value and remaining TTL to `f`, and
+    /// stores the value `f` returns (if any) before handing back its result.
+    ///
+    /// Stores using optimistic concurrency may call `f` again when the key was
+    /// modified concurrently, so it must not have side effects.
+    fn update<V, R, F>(&self, key: &str, f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R);
+
+    /// Atomically adds `by` to the counter under `key` and returns the new value.
+    ///
+    /// A missing counter starts at zero and expires after `ttl`; an existing
+    /// one keeps its expiration.
+    fn increment(&self, key: &str, by: u32, ttl: Duration) -> Result<u32, StoreError> {
+        self.update(key, |count: Option<u32>, remaining| {
+            let count = count.unwrap_or(0).saturating_add(by);
+            let ttl = remaining.unwrap_or(ttl);
+            (Some((count, ttl)), count)
+        })
+    }
+
+    /// Runs `algorithm` for `key` as a single server-side operation, recording
+    /// an attempt weighing `cost` as `op` requires.
+    ///
+    /// Returns `None` when the store has no native implementation of
+    /// `algorithm`, which is the default; the service then falls back to
+    /// [`update`](Self::update). Distributed backends override this to enforce
+    /// limits in one round trip.
+    fn run_native(
+        &self,
+        key: &str,
+        algorithm: Algorithm,
+        limit: u32,
+        period: Duration,
+        cost: u32,
+        op: Op,
+    ) -> Option<Result<Decision, StoreError>> {
+        let _ = (key, algorithm, limit, period, cost, op);
+        None
+    }
+}
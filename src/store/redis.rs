@@ -0,0 +1,288 @@
+use super::{Op, StoreError, StoreValue, ThrottleStore};
+use crate::algorithm;
+use crate::{Algorithm, Decision};
+use ::redis::{Client, Connection, Script};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+/// Fixed window: returns the remaining TTL and the count before this attempt.
+static FIXED_WINDOW: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local count = redis.call('GET', KEYS[1])
+        local ttl = redis.call('PTTL', KEYS[1])
+        local limit, period = tonumber(ARGV[1]), tonumber(ARGV[2])
+        local cost = tonumber(ARGV[4])
+        local current, reset = 0, period
+        if count and ttl > 0 then
+            current, reset = tonumber(count), ttl
+        end
+        if ARGV[3] == 'hit' or (ARGV[3] == 'attempt' and current + cost <= limit) then
+            redis.call('SET', KEYS[1], current + cost, 'PX', reset)
+        end
+        return {ttl, count or ''}
+        ",
+    )
+});
+
+/// Adds to a counter, setting its expiry only when it has none.
+static INCREMENT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local count = redis.call('INCRBY', KEYS[1], ARGV[1])
+        if redis.call('PTTL', KEYS[1]) < 0 then
+            redis.call('PEXPIRE', KEYS[1], ARGV[2])
+        end
+        return count
+        ",
+    )
+});
+
+/// Token bucket: returns the server time and the state before this attempt.
+static TOKEN_BUCKET: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local capacity, limit = tonumber(ARGV[1]), tonumber(ARGV[2])
+        local period = math.max(tonumber(ARGV[3]), 1)
+        local cost = tonumber(ARGV[5])
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local raw = redis.call('GET', KEYS[1])
+        local rate = limit / period
+        local tokens = capacity
+        if raw then
+            local state = cjson.decode(raw)
+            tokens = math.min(capacity, state[1] + math.max(now - state[2], 0) * rate)
+        end
+        if ARGV[4] == 'hit' or (ARGV[4] == 'attempt' and tokens >= cost) then
+            local left = tokens - cost
+            local ttl = math.max(math.ceil((capacity - left) / rate), 1)
+            redis.call('SET', KEYS[1], cjson.encode({left, now}), 'PX', ttl)
+        end
+        return {now, raw or ''}
+        ",
+    )
+});
+
+/// Stores throttling state in Redis, so every replica of a service enforces
+/// one shared limit.
+///
+/// Values are stored as JSON. [`Algorithm::FixedWindow`] and
+/// [`Algorithm::TokenBucket`] run as Lua scripts (invoked with `EVALSHA`) that
+/// check and record in one atomic round trip, timed by the Redis server clock
+/// for attempts and hits alike.
+/// Other algorithms go through [`update`](ThrottleStore::update), which uses
+/// `WATCH`/`MULTI`/`EXEC` and retries when the key changes concurrently.
+///
+/// Cloning a `RedisStore` yields another handle sharing the same connections.
+///
+/// Connections are synchronous, including when the store is used through
+/// `AsyncThrottleStore`: each round trip blocks the calling thread. On an
+/// async runtime, call the blocking methods from a blocking task, such as
+/// `tokio::task::spawn_blocking`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use throttle_ro::ThrottlesService;
+/// use throttle_ro::store::RedisStore;
+///
+/// let store = RedisStore::open("redis://127.0.0.1/").unwrap();
+/// let service = ThrottlesService::new("127.0.0.1".to_string(), 5, Duration::from_secs(60), "api_");
+///
+/// if service.attempt(&store).allowed {
+///     // Process the request
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "redis")))]
+#[derive(Clone)]
+pub struct RedisStore {
+    client: Client,
+    idle: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl RedisStore {
+    /// Creates a store that opens connections with `client` as needed.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            idle: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Creates a store connecting to the Redis server at `url`.
+    ///
+    /// # Errors
+    /// Returns an error if `url` is not a valid Redis connection URL.
+    pub fn open(url: &str) -> Result<Self, StoreError> {
+        Ok(Self::new(Client::open(url)?))
+    }
+
+    /// Runs `f` on an idle connection, opening one if none is available.
+    ///
+    /// Connections are only returned to the pool when `f` succeeds, so a
+    /// broken connection is never reused.
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut connection = match idle {
+            Some(connection) => connection,
+            None => self.client.get_connection()?,
+        };
+
+        let result = f(&mut connection);
+        if result.is_ok() {
+            self.idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(connection);
+        }
+        result
+    }
+}
+
+fn decode<V: StoreValue>(raw: Option<String>) -> Result<Option<V>, serde_json::Error> {
+    raw.filter(|raw| !raw.is_empty())
+        .map(|raw| serde_json::from_str(&raw))
+        .transpose()
+}
+
+/// Converts a `PTTL` reply, which is negative for missing or persistent keys.
+fn remaining(pttl: i64) -> Option<Duration> {
+    (pttl > 0).then(|| Duration::from_millis(pttl as u64))
+}
+
+impl ThrottleStore for RedisStore {
+    fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        self.with_connection(|con| {
+            let raw: Option<String> = ::redis::cmd("GET").arg(key).query(con)?;
+            Ok(decode(raw)?)
+        })
+    }
+
+    fn set<V: StoreValue>(&self, key: &str, value: V, ttl: Duration) -> Result<(), StoreError> {
+        let raw = serde_json::to_string(&value)?;
+        self.with_connection(|con| {
+            ::redis::cmd("SET")
+                .arg(key)
+                .arg(raw)
+                .arg("PX")
+                .arg(ttl.as_millis().max(1) as u64)
+                .exec(con)?;
+            Ok(())
+        })
+    }
+
+    fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        self.with_connection(|con| {
+            let pttl: i64 = ::redis::cmd("PTTL").arg(key).query(con)?;
+            Ok(remaining(pttl))
+        })
+    }
+
+    fn remove(&self, key: &str) -> Result<(), StoreError> {
+        self.with_connection(|con| {
+            ::redis::cmd("DEL").arg(key).exec(con)?;
+            Ok(())
+        })
+    }
+
+    fn update<V, R, F>(&self, key: &str, mut f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R),
+    {
+        self.with_connection(|con| {
+            let result = ::redis::transaction(con, &[key], |con, pipe| {
+                let (raw, pttl): (Option<String>, i64) = ::redis::pipe()
+                    .cmd("GET")
+                    .arg(key)
+                    .cmd("PTTL")
+                    .arg(key)
+                    .query(con)?;
+                let value = match decode(raw) {
+                    Ok(value) => value,
+                    Err(e) => return Ok(Some(Err(e))),
+                };
+
+                let (state, result) = f(value, remaining(pttl));
+                if let Some((value, ttl)) = state {
+                    let raw = match serde_json::to_string(&value) {
+                        Ok(raw) => raw,
+                        Err(e) => return Ok(Some(Err(e))),
+                    };
+                    pipe.cmd("SET")
+                        .arg(key)
+                        .arg(raw)
+                        .arg("PX")
+                        .arg(ttl.as_millis().max(1) as u64)
+                        .ignore();
+                }
+                let executed: Option<()> = pipe.query(con)?;
+                Ok(executed.map(|()| Ok(result)))
+            })?;
+            Ok(result?)
+        })
+    }
+
+    fn increment(&self, key: &str, by: u32, ttl: Duration) -> Result<u32, StoreError> {
+        self.with_connection(|con| {
+            let count = INCREMENT
+                .key(key)
+                .arg(by)
+                .arg(ttl.as_millis().max(1) as u64)
+                .invoke(con)?;
+            Ok(count)
+        })
+    }
+
+    fn run_native(
+        &self,
+        key: &str,
+        algorithm: Algorithm,
+        limit: u32,
+        period: Duration,
+        cost: u32,
+        op: Op,
+    ) -> Option<Result<Decision, StoreError>> {
+        let mode = match op {
+            Op::Check => "check",
+            Op::Attempt => "attempt",
+            Op::Hit => "hit",
+        };
+        let period_ms = period.as_millis() as u64;
+
+        match algorithm {
+            Algorithm::FixedWindow => Some(self.with_connection(|con| {
+                let (pttl, count): (i64, String) = FIXED_WINDOW
+                    .key(key)
+                    .arg(limit)
+                    .arg(period_ms)
+                    .arg(mode)
+                    .arg(cost)
+                    .invoke(con)?;
+                let count = decode::<u32>(Some(count))?;
+                let ttl = remaining(pttl);
+                Ok(algorithm::fixed_window(count, ttl, limit, period, cost, op).1)
+            })),
+            Algorithm::TokenBucket { capacity } => Some(self.with_connection(|con| {
+                let (now, state): (u64, String) = TOKEN_BUCKET
+                    .key(key)
+                    .arg(capacity)
+                    .arg(limit)
+                    .arg(period_ms)
+                    .arg(mode)
+                    .arg(cost)
+                    .invoke(con)?;
+                let state = decode(Some(state))?;
+                let decision =
+                    algorithm::token_bucket(state, now, capacity, limit, period, cost, op).1;
+                Ok(decision)
+            })),
+            _ => None,
+        }
+    }
+}
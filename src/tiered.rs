@@ -0,0 +1,260 @@
+use crate::clock::Clock;
+use crate::failure::FailureHook;
+use crate::key::AddressString;
+use crate::{
+    AccessList, Algorithm, Decision, FailurePolicy, KeyBuilder, ThrottleError, ThrottleKey,
+    ThrottleStore, ThrottlesService,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The outcome of checking every tier of a [`TieredThrottle`].
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TieredDecision {
+    /// Whether every tier admits the attempt.
+    pub allowed: bool,
+    /// Index of the tier that caused the rejection, if any.
+    ///
+    /// When several tiers are exhausted, this is the one that is blocked the longest.
+    pub rejected_by: Option<usize>,
+    /// The decision of each tier, in the order the tiers were configured.
+    pub tiers: Vec<Decision>,
+}
+
+impl TieredDecision {
+    fn new(tiers: Vec<Decision>) -> Self {
+        let rejected_by = tiers
+            .iter()
+            .enumerate()
+            .filter(|(_, decision)| !decision.allowed)
+            .max_by_key(|(_, decision)| decision.retry_after)
+            .map(|(index, _)| index);
+
+        Self {
+            allowed: rejected_by.is_none(),
+            rejected_by,
+            tiers,
+        }
+    }
+
+    /// Returns the decision that constrains the caller the most: the rejecting
+    /// tier, or otherwise the tier with the fewest remaining attempts.
+    pub fn binding(&self) -> &Decision {
+        match self.rejected_by {
+            Some(index) => &self.tiers[index],
+            None => self
+                .tiers
+                .iter()
+                .min_by_key(|decision| decision.remaining)
+                .expect("TieredThrottle has at least one tier"),
+        }
+    }
+}
+
+/// Enforces several limits on the same IP at once, e.g. 10 per second and
+/// 1000 per hour.
+///
+/// Each `(max_attempts, period)` tier is stored under its own key derived from
+/// the prefix, and an attempt is only admitted when every tier allows it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::TieredThrottle;
+/// use throttle_ro::store::MemoryStore;
+///
+/// let store = MemoryStore::new();
+/// let throttle = TieredThrottle::new(
+///     "127.0.0.1".to_string(),
+///     &[(10, Duration::from_secs(1)), (1000, Duration::from_secs(3600))],
+///     "api_",
+/// );
+///
+/// let decision = throttle.attempt(&store);
+/// if let Some(tier) = decision.rejected_by {
+///     println!("Rejected by tier {tier}, retry in {:?}", decision.binding().retry_after);
+/// }
+/// ```
+pub struct TieredThrottle {
+    tiers: Vec<(u32, Duration)>,
+    services: Vec<ThrottlesService>,
+}
+
+impl TieredThrottle {
+    /// Creates a new `TieredThrottle` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address to track
+    /// * `tiers` - `(max_attempts, period)` pairs that must all be satisfied
+    /// * `prefix` - Prefix for cache keys to avoid collisions
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tiers` is empty.
+    pub fn new(ip: String, tiers: &[(u32, Duration)], prefix: &str) -> Self {
+        Self::for_key(&AddressString(&ip), tiers, prefix)
+    }
+
+    /// Creates a `TieredThrottle` keyed by anything implementing [`ThrottleKey`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tiers` is empty.
+    pub fn for_key<K: ThrottleKey + ?Sized>(
+        key: &K,
+        tiers: &[(u32, Duration)],
+        prefix: &str,
+    ) -> Self {
+        assert!(!tiers.is_empty(), "TieredThrottle needs at least one tier");
+
+        // Every tier is cloned from one service, so they share its failure count.
+        let template = ThrottlesService::for_key(key, 0, Duration::ZERO, prefix);
+        let services = tiers
+            .iter()
+            .map(|&(max_attempts, period)| {
+                let mut tier = KeyBuilder::new();
+                tier.part(prefix)
+                    .part(&max_attempts.to_string())
+                    .part(&period.as_millis().to_string());
+                ThrottlesService {
+                    max_attempts,
+                    period,
+                    prefix: tier.build(),
+                    ..template.clone()
+                }
+            })
+            .collect();
+
+        Self {
+            tiers: tiers.to_vec(),
+            services,
+        }
+    }
+
+    /// Creates a `TieredThrottle` for a parsed IP address, aggregated like
+    /// [`ThrottlesService::for_ip`].
+    pub fn for_ip(ip: IpAddr, tiers: &[(u32, Duration)], prefix: &str) -> Self {
+        Self::for_key(&ip, tiers, prefix)
+    }
+
+    /// Selects the algorithm used by every tier.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.map_services(|service| service.with_algorithm(algorithm));
+        self
+    }
+
+    /// Sets the clock every tier reads the current time from.
+    pub fn with_clock<C: Clock + Clone + 'static>(mut self, clock: C) -> Self {
+        self.map_services(|service| service.with_clock(clock.clone()));
+        self
+    }
+
+    /// Consults `access` in every tier before counting any attempt.
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.map_services(|service| service.with_access_list(access.clone()));
+        self
+    }
+
+    /// Selects what every tier answers when the store fails.
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.map_services(|service| service.with_failure_policy(policy));
+        self
+    }
+
+    /// Calls `hook` with every store error the tiers' [`FailurePolicy`] handles.
+    pub fn with_failure_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        let hook: FailureHook = Arc::new(hook);
+        self.map_services(|service| service.with_shared_failure_hook(hook.clone()));
+        self
+    }
+
+    /// Returns how many store errors the failure policy has handled, across
+    /// every tier.
+    pub fn failures(&self) -> u64 {
+        self.services[0].failures()
+    }
+
+    /// Returns the configured `(max_attempts, period)` tiers.
+    pub fn tiers(&self) -> &[(u32, Duration)] {
+        &self.tiers
+    }
+
+    /// Reports the status of every tier without recording an attempt.
+    pub fn check<S: ThrottleStore>(&self, store: &S) -> TieredDecision {
+        TieredDecision::new(self.services.iter().map(|s| s.check(store)).collect())
+    }
+
+    /// Like [`check`](Self::check), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_check<S: ThrottleStore>(&self, store: &S) -> Result<TieredDecision, ThrottleError> {
+        let tiers = self.services.iter().map(|s| s.try_check(store));
+        Ok(TieredDecision::new(tiers.collect::<Result<_, _>>()?))
+    }
+
+    /// Records the attempt in every tier if all of them allow it.
+    ///
+    /// Tiers are checked first and only recorded when none is exhausted, so a
+    /// rejection does not consume budget in the other tiers. Concurrent attempts
+    /// racing for the last slot of a tier may still be counted by the tiers
+    /// recorded before it, which errs on the side of throttling.
+    pub fn attempt<S: ThrottleStore>(&self, store: &S) -> TieredDecision {
+        let checked = self.check(store);
+        if !checked.allowed {
+            return checked;
+        }
+        TieredDecision::new(self.services.iter().map(|s| s.attempt(store)).collect())
+    }
+
+    /// Like [`attempt`](Self::attempt), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_attempt<S: ThrottleStore>(
+        &self,
+        store: &S,
+    ) -> Result<TieredDecision, ThrottleError> {
+        let checked = self.try_check(store)?;
+        if !checked.allowed {
+            return Ok(checked);
+        }
+        let tiers = self.services.iter().map(|s| s.try_attempt(store));
+        Ok(TieredDecision::new(tiers.collect::<Result<_, _>>()?))
+    }
+
+    /// Records an attempt in every tier.
+    pub fn hit<S: ThrottleStore>(&mut self, store: &S) {
+        for service in &mut self.services {
+            service.hit(store);
+        }
+    }
+
+    /// Like [`hit`](Self::hit), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_hit<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        self.services.iter().try_for_each(|s| s.try_hit(store))
+    }
+
+    /// Clears the attempt counts of every tier.
+    pub fn remove<S: ThrottleStore>(&self, store: &S) {
+        for service in &self.services {
+            service.remove(store);
+        }
+    }
+
+    /// Like [`remove`](Self::remove), but returns store errors instead of
+    /// applying the failure policy.
+    pub fn try_remove<S: ThrottleStore>(&self, store: &S) -> Result<(), ThrottleError> {
+        self.services.iter().try_for_each(|s| s.try_remove(store))
+    }
+
+    fn map_services(&mut self, f: impl Fn(ThrottlesService) -> ThrottlesService) {
+        for service in &mut self.services {
+            *service = f(service.clone());
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! [Tower](https://docs.rs/tower) middleware that throttles every request
+//! passing through a service stack, such as an axum router or a tonic server.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use http::Request;
+//! use throttle_ro::store::MemoryStore;
+//! use throttle_ro::tower::ThrottleLayer;
+//!
+//! // 100 requests per minute per API key; requests without one pass through.
+//! let layer = ThrottleLayer::new(
+//!     MemoryStore::new(),
+//!     100,
+//!     Duration::from_secs(60),
+//!     "api_",
+//!     |request: &Request<String>| {
+//!         request
+//!             .headers()
+//!             .get("x-api-key")
+//!             .and_then(|key| key.to_str().ok())
+//!             .map(str::to_string)
+//!     },
+//! );
+//! ```
+
+use crate::headers::seconds;
+use crate::{
+    AccessList, Algorithm, Decision, FailurePolicy, HeaderDialect, ThrottleError, ThrottleKey,
+    ThrottleStore, ThrottlesService,
+};
+use http::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use http::{Request, Response, StatusCode};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The response sent back when a request is throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// An empty HTTP response with this status and a `Retry-After` header.
+    Status(StatusCode),
+    /// A trailers-only gRPC response with status `RESOURCE_EXHAUSTED` (8).
+    Grpc,
+}
+
+impl Default for Rejection {
+    /// `429 Too Many Requests`.
+    fn default() -> Self {
+        Self::Status(StatusCode::TOO_MANY_REQUESTS)
+    }
+}
+
+impl Rejection {
+    fn response<B: Default>(&self, decision: &Decision) -> Response<B> {
+        let mut response = Response::new(B::default());
+        match self {
+            Self::Status(status) => *response.status_mut() = *status,
+            Self::Grpc => {
+                let headers = response.headers_mut();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+                headers.insert("grpc-status", HeaderValue::from_static("8"));
+                headers.insert(
+                    "grpc-message",
+                    HeaderValue::from_static("rate%20limit%20exceeded"),
+                );
+            }
+        }
+        if let Some(retry_after) = decision.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds(retry_after)));
+        }
+        response
+    }
+}
+
+/// A [`Layer`] that throttles requests by a key extracted from each request.
+///
+/// The extractor returns the [`ThrottleKey`] of a request, e.g. the client
+/// address or an API token, or `None` to let the request through unthrottled.
+/// Every request is checked and recorded in one atomic
+/// [`attempt`](ThrottlesService::attempt); rejected requests never reach the
+/// inner service.
+///
+/// Store operations are synchronous, so prefer a fast store such as
+/// [`MemoryStore`](crate::store::MemoryStore) on async runtimes.
+pub struct ThrottleLayer<St, F> {
+    store: Arc<St>,
+    extractor: Arc<F>,
+    service: ThrottlesService,
+    rejection: Rejection,
+    headers: Option<HeaderDialect>,
+}
+
+impl<St, F> ThrottleLayer<St, F> {
+    /// Creates a layer allowing `max_attempts` per `period` for each key.
+    pub fn new(store: St, max_attempts: u32, period: Duration, prefix: &str, extractor: F) -> Self {
+        Self {
+            store: Arc::new(store),
+            extractor: Arc::new(extractor),
+            service: ThrottlesService::for_key("", max_attempts, period, prefix),
+            rejection: Rejection::default(),
+            headers: None,
+        }
+    }
+
+    /// Selects the algorithm used to enforce the limit.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.service = self.service.with_algorithm(algorithm);
+        self
+    }
+
+    /// Consults `access` before counting any request.
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.service = self.service.with_access_list(access);
+        self
+    }
+
+    /// Selects what to answer when the store fails, see [`FailurePolicy`].
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.service = self.service.with_failure_policy(policy);
+        self
+    }
+
+    /// Calls `hook` with every store error the [`FailurePolicy`] handles.
+    pub fn with_failure_hook<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&ThrottleError) + Send + Sync + 'static,
+    {
+        self.service = self.service.with_failure_hook(hook);
+        self
+    }
+
+    /// Returns how many store errors the failure policy has handled.
+    pub fn failures(&self) -> u64 {
+        self.service.failures()
+    }
+
+    /// Sets the response sent back for throttled requests.
+    ///
+    /// Defaults to `429 Too Many Requests`; use [`Rejection::Grpc`] for tonic.
+    pub fn with_rejection(mut self, rejection: Rejection) -> Self {
+        self.rejection = rejection;
+        self
+    }
+
+    /// Adds rate-limit headers in `dialect` to every throttled response.
+    ///
+    /// Without it, only rejections carry a `Retry-After` header.
+    pub fn with_headers(mut self, dialect: HeaderDialect) -> Self {
+        self.headers = Some(dialect);
+        self
+    }
+
+    fn headers(&self, decision: &Decision) -> HeaderMap {
+        let Some(dialect) = self.headers else {
+            return HeaderMap::new();
+        };
+        self.service
+            .headers(decision, dialect)
+            .into_iter()
+            .map(|(name, value)| {
+                let name = HeaderName::from_bytes(name.as_bytes()).expect("valid header name");
+                let value = HeaderValue::try_from(value).expect("valid header value");
+                (name, value)
+            })
+            .collect()
+    }
+}
+
+impl<St, F> Clone for ThrottleLayer<St, F> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            extractor: self.extractor.clone(),
+            service: self.service.clone(),
+            rejection: self.rejection,
+            headers: self.headers,
+        }
+    }
+}
+
+impl<S, St, F> Layer<S> for ThrottleLayer<St, F> {
+    type Service = Throttle<S, St, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Throttle {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The [`Service`] produced by [`ThrottleLayer`].
+pub struct Throttle<S, St, F> {
+    inner: S,
+    layer: ThrottleLayer<St, F>,
+}
+
+impl<S: Clone, St, F> Clone for Throttle<S, St, F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, St, F, K, ReqBody, ResBody> Service<Request<ReqBody>> for Throttle<S, St, F>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    St: ThrottleStore,
+    F: Fn(&Request<ReqBody>) -> Option<K>,
+    K: ThrottleKey,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let layer = &self.layer;
+        let mut headers = HeaderMap::new();
+        if let Some(key) = (layer.extractor)(&request) {
+            let decision = layer.service.rekey(&key).attempt(&*layer.store);
+            headers = layer.headers(&decision);
+            if !decision.allowed {
+                let mut response = layer.rejection.response(&decision);
+                response.headers_mut().extend(headers);
+                return ResponseFuture::Rejected {
+                    response: Some(response),
+                };
+            }
+        }
+        ResponseFuture::Inner {
+            future: self.inner.call(request),
+            headers: Some(headers),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [`Throttle`].
+    #[project = ResponseFutureProj]
+    pub enum ResponseFuture<Fut, B> {
+        /// The request was admitted and is being handled by the inner service.
+        Inner {
+            #[pin]
+            future: Fut,
+            headers: Option<HeaderMap>,
+        },
+        /// The request was throttled.
+        Rejected {
+            response: Option<Response<B>>,
+        },
+    }
+}
+
+impl<Fut, B, E> Future for ResponseFuture<Fut, B>
+where
+    Fut: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Inner { future, headers } => {
+                let mut response = ready!(future.poll(cx))?;
+                if let Some(headers) = headers.take() {
+                    response.headers_mut().extend(headers);
+                }
+                Poll::Ready(Ok(response))
+            }
+            ResponseFutureProj::Rejected { response } => Poll::Ready(Ok(response
+                .take()
+                .expect("ResponseFuture polled after completion"))),
+        }
+    }
+}
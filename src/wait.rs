@@ -0,0 +1,68 @@
+use crate::Decision;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/// How [`ThrottlesService::wait_until_ready`](crate::ThrottlesService::wait_until_ready),
+/// and `until_ready_with` with the `tokio` feature, wait for the limiter to
+/// admit an attempt.
+///
+/// By default they wait as long as it takes, retrying exactly when the
+/// rejection's `retry_after` has passed.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use throttle_ro::Wait;
+///
+/// // Spread retries over an extra 100ms so that many waiting clients don't
+/// // all retry at once, and give up after 5 seconds.
+/// let wait = Wait::new()
+///     .jitter(Duration::from_millis(100))
+///     .timeout(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wait {
+    jitter: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Wait {
+    /// Waits without jitter or deadline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a random delay of up to `jitter` to every retry.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Gives up, returning the rejection, when the next retry would be after `deadline`.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Gives up after `timeout` from now, see [`deadline`](Self::deadline).
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Returns how long to sleep before retrying after `decision`, or `None`
+    /// to give up.
+    pub(crate) fn backoff(&self, decision: &Decision) -> Option<Duration> {
+        let mut wait = decision.retry_after?;
+        if !self.jitter.is_zero() {
+            let random = RandomState::new().build_hasher().finish();
+            wait +=
+                Duration::from_nanos(random % self.jitter.as_nanos().min(u64::MAX as u128) as u64);
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() + wait > deadline => None,
+            _ => Some(wait),
+        }
+    }
+}
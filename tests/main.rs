@@ -1,17 +1,1416 @@
+#[cfg(feature = "cache-ro")]
 use cache_ro::{Cache, CacheConfig};
-use std::thread::sleep;
-use std::time::Duration;
-use throttle_ro::ThrottlesService;
+use std::any::Any;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, sleep};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use throttle_ro::clock::ManualClock;
+use throttle_ro::store::MemoryStore;
+use throttle_ro::{
+    Access, AccessList, AccessRules, Algorithm, FailurePolicy, ForwardedHeader, HeaderDialect,
+    IpAggregation, IpNet, KeyBuilder, Lockout, LoginCounter, LoginGuard, StoreError, StoreValue,
+    ThrottleError, ThrottleKey, ThrottleStore, ThrottlesService, TieredThrottle, TrustedProxies,
+    Wait,
+};
+
+type Entry = (Box<dyn Any + Send + Sync>, Instant);
+
+/// A minimal store proving that the limiter runs without the global cache.
+#[derive(Default)]
+struct MapStore(Mutex<HashMap<String, Entry>>);
+
+impl ThrottleStore for MapStore {
+    fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        let map = self.0.lock().unwrap();
+        Ok(map
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .and_then(|(value, _)| value.downcast_ref::<V>().cloned()))
+    }
+
+    fn set<V: StoreValue>(&self, key: &str, value: V, ttl: Duration) -> Result<(), StoreError> {
+        let entry: Entry = (Box::new(value), Instant::now() + ttl);
+        self.0.lock().unwrap().insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        let map = self.0.lock().unwrap();
+        Ok(map
+            .get(key)
+            .and_then(|(_, expires_at)| expires_at.checked_duration_since(Instant::now())))
+    }
+
+    fn remove(&self, key: &str) -> Result<(), StoreError> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn update<V, R, F>(&self, key: &str, mut f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R),
+    {
+        let mut map = self.0.lock().unwrap();
+        let now = Instant::now();
+        let current = map.get(key).filter(|(_, expires_at)| *expires_at > now);
+        let value = current.and_then(|(value, _)| value.downcast_ref::<V>().cloned());
+        let ttl = current.map(|(_, expires_at)| *expires_at - now);
+
+        let (state, result) = f(value, ttl);
+        if let Some((value, ttl)) = state {
+            map.insert(key.to_string(), (Box::new(value), now + ttl));
+        }
+        Ok(result)
+    }
+}
+
+#[test]
+fn test_custom_store() {
+    let store = MapStore::default();
+    let mut service =
+        ThrottlesService::new("127.0.0.1".to_string(), 2, Duration::from_secs(60), "test_");
+
+    service.hit(&store);
+    assert_eq!(store.get::<u32>(&service.key()).unwrap(), Some(1));
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+    assert!(service.get_expire(&store) <= Duration::from_secs(60));
+
+    service.remove(&store);
+    assert!(service.can_go(&store));
+}
+
+#[test]
+fn test_memory_store_expires_lazily() {
+    let store = MemoryStore::new();
+    let service = ThrottlesService::new(
+        "127.0.0.1".to_string(),
+        1,
+        Duration::from_millis(50),
+        "test_",
+    );
+
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+    assert_eq!(store.len(), 1);
+
+    sleep(Duration::from_millis(60));
+    assert_eq!(store.ttl(&service.key()).unwrap(), None);
+    assert!(service.attempt(&store).allowed);
+
+    sleep(Duration::from_millis(60));
+    store.purge_expired();
+    assert!(store.is_empty());
+}
+
+#[test]
+fn test_memory_store_is_atomic_under_contention() {
+    let store = MemoryStore::new();
+    let allowed = AtomicU32::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                let mut service = ThrottlesService::new(
+                    "127.0.0.1".to_string(),
+                    50,
+                    Duration::from_secs(60),
+                    "test_",
+                );
+                let other = ThrottlesService::new(
+                    "127.0.0.2".to_string(),
+                    50,
+                    Duration::from_secs(60),
+                    "test_",
+                )
+                .with_algorithm(Algorithm::TokenBucket { capacity: 50 });
+                for _ in 0..100 {
+                    service.hit(&store);
+                    if other.attempt(&store).allowed {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(store.get::<u32>("5:test_9:127.0.0.1").unwrap(), Some(800));
+    assert!((50..=52).contains(&allowed.load(Ordering::SeqCst)));
+}
+
+#[test]
+fn test_memory_store_fixed_window_attempts_are_exact() {
+    let store = MemoryStore::new();
+    let service = ThrottlesService::new(
+        "127.0.0.1".to_string(),
+        50,
+        Duration::from_secs(60),
+        "test_",
+    );
+    let allowed = AtomicU32::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..100 {
+                    if service.attempt_with_cost(&store, 2).allowed {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(allowed.load(Ordering::SeqCst), 25);
+    assert_eq!(store.get::<u32>(&service.key()).unwrap(), Some(50));
+}
+
+#[test]
+fn test_memory_store_saturates_huge_ttls() {
+    let store = MemoryStore::new();
+    let forever = ThrottlesService::new("127.0.0.1".to_string(), 1, Duration::MAX, "test_");
+    assert!(forever.attempt(&store).allowed);
+    assert!(!forever.attempt(&store).allowed);
+
+    let never = ThrottlesService::new("127.0.0.2".to_string(), 0, Duration::from_secs(1), "test_")
+        .with_algorithm(Algorithm::TokenBucket { capacity: 1 });
+    assert!(never.attempt(&store).allowed);
+    assert!(!never.attempt(&store).allowed);
+}
+
+fn manual(
+    name: &str,
+    max_attempts: u32,
+    period: Duration,
+) -> (ManualClock, MemoryStore, ThrottlesService) {
+    let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let store = MemoryStore::with_clock(clock.clone());
+    let service = ThrottlesService::new(name.to_string(), max_attempts, period, "test_")
+        .with_clock(clock.clone());
+    (clock, store, service)
+}
+
+#[test]
+fn test_fixed_window_spans_a_day() {
+    let day = Duration::from_secs(86_400);
+    let (clock, store, service) = manual("127.0.0.1", 3, day);
+
+    for _ in 0..3 {
+        assert!(service.attempt(&store).allowed);
+    }
+    clock.advance(Duration::from_secs(3_600));
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(day - Duration::from_secs(3_600)));
+
+    clock.advance(day - Duration::from_secs(3_600));
+    assert_eq!(service.attempt(&store).remaining, 2);
+}
+
+#[test]
+fn test_token_bucket_allows_burst_then_refills() {
+    let (clock, store, service) = manual("127.0.0.1", 1, Duration::from_secs(60));
+    let service = service.with_algorithm(Algorithm::TokenBucket { capacity: 3 });
+
+    assert_eq!(service.check(&store).remaining, 3);
+    assert_eq!(service.attempt(&store).remaining, 2);
+    assert!(service.attempt(&store).allowed);
+    assert!(service.attempt(&store).allowed);
+
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.limit, 3);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(60)));
+    assert_eq!(decision.reset_after, Duration::from_secs(180));
+
+    clock.advance(Duration::from_secs(90));
+    assert!(service.attempt(&store).allowed);
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(30)));
+}
+
+#[test]
+fn test_sliding_window_log_rolls() {
+    let (clock, store, service) = manual("127.0.0.1", 2, Duration::from_secs(3_600));
+    let mut service = service.with_algorithm(Algorithm::SlidingWindowLog);
+
+    assert!(service.attempt(&store).allowed);
+    clock.advance(Duration::from_secs(1_800));
+    assert_eq!(service.attempt(&store).remaining, 0);
+
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(1_800)));
+    assert_eq!(decision.reset_after, Duration::from_secs(3_600));
+
+    clock.advance(Duration::from_secs(1_800));
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.can_go(&store));
+}
+
+#[test]
+fn test_sliding_window_counter_weights_previous_window() {
+    let (clock, store, service) = manual("127.0.0.1", 4, Duration::from_secs(100));
+    let mut service = service.with_algorithm(Algorithm::SlidingWindowCounter);
+
+    for _ in 0..4 {
+        service.hit(&store);
+    }
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.reset_after, Duration::from_secs(100));
+
+    // A quarter into the next window, three quarters of the previous four still count.
+    clock.advance(Duration::from_secs(125));
+    assert_eq!(service.check(&store).remaining, 1);
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+
+    // The estimate drops below the limit once the previous window weighs less than 3/4.
+    clock.advance(Duration::from_secs(5));
+    assert!(service.attempt(&store).allowed);
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(Duration::from_millis(20_001)));
+
+    clock.advance(Duration::from_secs(200));
+    assert_eq!(service.check(&store).remaining, 4);
+}
+
+#[test]
+fn test_gcra_spaces_attempts_after_burst() {
+    let (clock, store, service) = manual("127.0.0.1", 1, Duration::from_secs(10));
+    let service = service.with_algorithm(Algorithm::Gcra { burst: 2 });
+
+    assert_eq!(service.attempt(&store).remaining, 1);
+    assert_eq!(service.attempt(&store).remaining, 0);
+
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.limit, 2);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(10)));
+    assert_eq!(decision.reset_after, Duration::from_secs(20));
+
+    clock.advance(Duration::from_secs(10));
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+}
+
+#[test]
+fn test_leaky_bucket_delays_then_rejects() {
+    let (clock, store, service) = manual("127.0.0.1", 10, Duration::from_secs(1));
+    let service = service.with_algorithm(Algorithm::LeakyBucket { max_queue: 2 });
+
+    let first = service.attempt(&store);
+    assert!(first.allowed);
+    assert_eq!(first.delay, Duration::ZERO);
+    assert_eq!(service.attempt(&store).delay, Duration::from_millis(100));
+
+    let third = service.attempt(&store);
+    assert!(third.allowed);
+    assert_eq!(third.remaining, 0);
+    assert_eq!(third.delay, Duration::from_millis(200));
+
+    let fourth = service.attempt(&store);
+    assert!(!fourth.allowed);
+    assert_eq!(fourth.retry_after, Some(Duration::from_millis(100)));
+
+    clock.advance(Duration::from_millis(100));
+    assert_eq!(service.attempt(&store).delay, Duration::from_millis(200));
+}
+
+#[test]
+fn test_weighted_costs() {
+    let (clock, store, service) = manual("fixed", 10, Duration::from_secs(60));
+    assert_eq!(service.attempt_with_cost(&store, 8).remaining, 2);
+    let decision = service.attempt_with_cost(&store, 3);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(60)));
+    assert_eq!(service.check(&store).remaining, 2);
+    service.hit_n(&store, 2);
+    assert_eq!(service.check(&store).remaining, 0);
+
+    // A cost over the capacity can never fit.
+    let decision = service.attempt_with_cost(&store, 11);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, None);
+
+    let service = ThrottlesService::new("bucket".to_string(), 1, Duration::from_secs(10), "test_")
+        .with_clock(clock.clone())
+        .with_algorithm(Algorithm::TokenBucket { capacity: 10 });
+    assert_eq!(service.attempt_with_cost(&store, 6).remaining, 4);
+    let decision = service.attempt_with_cost(&store, 6);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(20)));
+    clock.advance(Duration::from_secs(20));
+    assert_eq!(service.attempt_with_cost(&store, 6).remaining, 0);
+    assert_eq!(service.attempt_with_cost(&store, 11).retry_after, None);
+
+    let service = ThrottlesService::new("log".to_string(), 5, Duration::from_secs(100), "test_")
+        .with_clock(clock.clone())
+        .with_algorithm(Algorithm::SlidingWindowLog);
+    assert!(service.attempt_with_cost(&store, 2).allowed);
+    clock.advance(Duration::from_secs(10));
+    assert!(service.attempt_with_cost(&store, 2).allowed);
+    clock.advance(Duration::from_secs(10));
+    let decision = service.attempt_with_cost(&store, 3);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(80)));
+    clock.advance(Duration::from_secs(80));
+    assert_eq!(service.attempt_with_cost(&store, 3).remaining, 0);
+
+    let (clock, store, service) = manual("counter", 4, Duration::from_secs(100));
+    let service = service.with_algorithm(Algorithm::SlidingWindowCounter);
+    service.hit_n(&store, 4);
+    clock.advance(Duration::from_secs(110));
+    let decision = service.attempt_with_cost(&store, 2);
+    assert_eq!(decision.retry_after, Some(Duration::from_millis(15_001)));
+    clock.advance(Duration::from_millis(15_001));
+    assert!(service.attempt_with_cost(&store, 2).allowed);
+    assert!(!service.attempt(&store).allowed);
+
+    let service = ThrottlesService::new("gcra".to_string(), 1, Duration::from_secs(10), "test_")
+        .with_clock(clock.clone())
+        .with_algorithm(Algorithm::Gcra { burst: 3 });
+    assert_eq!(service.attempt_with_cost(&store, 3).remaining, 0);
+    let decision = service.attempt_with_cost(&store, 2);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(20)));
+    assert_eq!(service.attempt_with_cost(&store, 4).retry_after, None);
+
+    let service = ThrottlesService::new("leaky".to_string(), 10, Duration::from_secs(1), "test_")
+        .with_clock(clock.clone())
+        .with_algorithm(Algorithm::LeakyBucket { max_queue: 3 });
+    let decision = service.attempt_with_cost(&store, 3);
+    assert_eq!((decision.remaining, decision.delay), (1, Duration::ZERO));
+    let decision = service.attempt_with_cost(&store, 2);
+    assert_eq!(decision.retry_after, Some(Duration::from_millis(100)));
+    clock.advance(Duration::from_millis(100));
+    let decision = service.attempt_with_cost(&store, 2);
+    assert_eq!(decision.delay, Duration::from_millis(200));
+    assert_eq!(service.attempt_with_cost(&store, 5).retry_after, None);
+
+    // A limit of 0 blocks everything, as with the window algorithms.
+    for algorithm in [
+        Algorithm::FixedWindow,
+        Algorithm::SlidingWindowLog,
+        Algorithm::SlidingWindowCounter,
+        Algorithm::Gcra { burst: 3 },
+        Algorithm::LeakyBucket { max_queue: 3 },
+    ] {
+        let service = ThrottlesService::new("zero".to_string(), 0, Duration::from_secs(1), "test_")
+            .with_clock(clock.clone())
+            .with_algorithm(algorithm);
+        let decision = service.attempt(&store);
+        assert!(!decision.allowed, "{algorithm:?}");
+        assert_eq!(decision.retry_after, None, "{algorithm:?}");
+    }
+
+    // Extreme limits and costs saturate instead of overflowing.
+    for algorithm in [
+        Algorithm::FixedWindow,
+        Algorithm::TokenBucket { capacity: u32::MAX },
+        Algorithm::SlidingWindowLog,
+        Algorithm::SlidingWindowCounter,
+        Algorithm::Gcra { burst: u32::MAX },
+        Algorithm::LeakyBucket {
+            max_queue: u32::MAX,
+        },
+    ] {
+        let key = format!("{algorithm:?}");
+        let service = ThrottlesService::new(key, u32::MAX, Duration::from_secs(1), "max_")
+            .with_clock(clock.clone())
+            .with_algorithm(algorithm);
+        let _ = service.check(&store);
+        let _ = service.attempt_with_cost(&store, 1);
+        let _ = service.attempt_with_cost(&store, u32::MAX);
+
+        let service = ThrottlesService::new("huge".to_string(), 2, Duration::from_secs(1), "max_")
+            .with_clock(clock.clone())
+            .with_algorithm(algorithm);
+        service.hit_n(&store, u32::MAX);
+        // The huge leaky bucket queues the next attempt instead of rejecting it.
+        let decision = service.check(&store);
+        assert!(
+            !decision.allowed || decision.delay > Duration::from_secs(3600),
+            "{algorithm:?}"
+        );
+        service.remove(&store);
+
+        let service = ThrottlesService::new("forever".to_string(), 2, Duration::MAX, "max_")
+            .with_clock(clock.clone())
+            .with_algorithm(algorithm);
+        assert!(service.attempt(&store).allowed, "{algorithm:?}");
+        service.hit_n(&store, u32::MAX);
+        clock.advance(Duration::from_secs(1));
+        let _ = service.attempt_with_cost(&store, u32::MAX);
+        service.remove(&store);
+    }
+}
+
+#[test]
+fn test_tiered_rejects_on_exhausted_tier() {
+    let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let store = MemoryStore::with_clock(clock.clone());
+    let throttle = TieredThrottle::new(
+        "127.0.0.1".to_string(),
+        &[(2, Duration::from_secs(1)), (3, Duration::from_secs(3_600))],
+        "test_",
+    )
+    .with_clock(clock.clone());
+
+    assert!(throttle.attempt(&store).allowed);
+    let decision = throttle.attempt(&store);
+    assert!(decision.allowed);
+    assert_eq!(decision.binding().remaining, 0);
+
+    let decision = throttle.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.rejected_by, Some(0));
+    assert_eq!(decision.tiers[1].remaining, 1);
+
+    clock.advance(Duration::from_secs(1));
+    assert!(throttle.attempt(&store).allowed);
+    let decision = throttle.attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.rejected_by, Some(1));
+    assert_eq!(decision.binding().limit, 3);
+
+    throttle.remove(&store);
+    assert!(throttle.check(&store).allowed);
+
+    // Tier keys never collide across prefixes and limits.
+    let second = Duration::from_secs(1);
+    let a = TieredThrottle::new("127.0.0.1".to_string(), &[(15, second)], "api_");
+    let b = TieredThrottle::new("127.0.0.1".to_string(), &[(5, second)], "api_1");
+    for _ in 0..5 {
+        assert!(b.attempt(&store).allowed);
+    }
+    assert_eq!(a.check(&store).binding().remaining, 15);
+}
+
+#[test]
+fn test_ip_spellings_share_a_bucket() {
+    let store = MemoryStore::new();
+    let period = Duration::from_secs(60);
+    let ips = [
+        "::ffff:127.0.0.1",
+        "127.0.0.1",
+        "::1",
+        "0:0:0:0:0:0:0:1",
+        "::2",
+    ];
+
+    for ip in ips {
+        let service = ThrottlesService::for_ip(ip.parse().unwrap(), 3, period, "test_");
+        assert!(service.attempt(&store).allowed);
+    }
+
+    let v4 = ThrottlesService::for_ip("127.0.0.1".parse().unwrap(), 3, period, "test_");
+    assert_eq!(v4.key(), "5:test_9:127.0.0.1");
+    assert_eq!(v4.check(&store).remaining, 1);
+
+    let v6 = ThrottlesService::for_ip("::3".parse().unwrap(), 3, period, "test_");
+    assert_eq!(v6.key(), "5:test_5:::/64");
+    assert!(!v6.attempt(&store).allowed);
+}
+
+#[test]
+fn test_ip_aggregation_prefixes() {
+    let aggregation = IpAggregation::new(24, 48);
+    assert_eq!(aggregation.key("10.1.2.3".parse().unwrap()), "10.1.2.0/24");
+    assert_eq!(
+        aggregation.key("2001:db8:1:2:3::4".parse().unwrap()),
+        "2001:db8:1::/48"
+    );
+    assert_eq!(
+        IpAggregation::NONE.key("2001:db8::1".parse().unwrap()),
+        "2001:db8::1"
+    );
+    assert_eq!(
+        IpAggregation::new(0, 0).key("10.1.2.3".parse().unwrap()),
+        "0.0.0.0/0"
+    );
+
+    // Aggregated services share a bucket but still match the access list.
+    let store = MemoryStore::new();
+    let period = Duration::from_secs(60);
+    let service = |ip| ThrottlesService::for_ip_with(ip, aggregation, 1, period, "test_");
+    assert!(service(ip("10.1.2.3")).attempt(&store).allowed);
+    assert!(!service(ip("10.1.2.4")).attempt(&store).allowed);
+
+    let access = AccessList::new(AccessRules::new().allow("10.1.2.4".parse().unwrap()));
+    let allowed = service(ip("10.1.2.4")).with_access_list(access);
+    assert!(allowed.attempt(&store).allowed);
+}
+
+#[test]
+fn test_keys_are_unambiguous() {
+    let period = Duration::from_secs(60);
+    let a = ThrottlesService::new("2".to_string(), 1, period, "a1");
+    let b = ThrottlesService::new("12".to_string(), 1, period, "a");
+    assert_ne!(a.key(), b.key());
+
+    let a = ThrottlesService::for_key(&("a:b", "c"), 1, period, "p");
+    let b = ThrottlesService::for_key(&("a", "b:c"), 1, period, "p");
+    assert_ne!(a.key(), b.key());
+}
+
+struct Route<'a> {
+    user: u64,
+    path: &'a str,
+}
+
+impl ThrottleKey for Route<'_> {
+    fn append_to(&self, key: &mut KeyBuilder) {
+        key.part("route").key(&self.user).part(self.path);
+    }
+}
+
+#[test]
+fn test_composite_keys() {
+    let store = MemoryStore::new();
+    let period = Duration::from_secs(60);
+    let route = Route {
+        user: 7,
+        path: "/export",
+    };
+
+    let service = ThrottlesService::for_key(&route, 1, period, "test_");
+    assert_eq!(service.key(), "5:test_5:route1:77:/export");
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+
+    let other_route = ThrottlesService::for_key(&(7u64, "/search"), 1, period, "test_");
+    assert!(other_route.attempt(&store).allowed);
+
+    let tiered = TieredThrottle::for_key(&route, &[(1, period)], "tiered_");
+    assert!(tiered.attempt(&store).allowed);
+}
+
+#[test]
+fn test_ip_nets() {
+    let net: IpNet = "10.1.2.3/8".parse().unwrap();
+    assert_eq!(net.to_string(), "10.0.0.0/8");
+    assert!(net.contains("10.200.0.1".parse().unwrap()));
+    assert!(net.contains("::ffff:10.0.0.1".parse().unwrap()));
+    assert!(!net.contains("11.0.0.1".parse().unwrap()));
+
+    let mapped: IpNet = "::ffff:192.168.0.0/112".parse().unwrap();
+    assert_eq!(mapped.to_string(), "192.168.0.0/16");
+
+    assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+    assert!("10.0.0.0/".parse::<IpNet>().is_err());
+    assert!("example.com".parse::<IpNet>().is_err());
+}
+
+#[test]
+fn test_access_list_short_circuits() {
+    let store = MemoryStore::new();
+    let period = Duration::from_secs(60);
+    let access = AccessList::new(
+        AccessRules::new()
+            .allow("10.0.0.0/8".parse().unwrap())
+            .deny("203.0.113.0/24".parse().unwrap())
+            .allow("203.0.113.7".parse().unwrap())
+            .deny("2001:db8::/32".parse().unwrap()),
+    );
+    let service = |ip: &str| {
+        ThrottlesService::new(ip.to_string(), 1, period, "test_").with_access_list(access.clone())
+    };
+
+    let mut allowed = service("10.1.2.3");
+    for _ in 0..3 {
+        let decision = allowed.attempt(&store);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+    }
+    allowed.hit(&store);
+    assert!(store.is_empty());
+
+    let denied = service("203.0.113.8");
+    let decision = denied.check(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, None);
+    assert!(service("203.0.113.7").attempt(&store).allowed);
+    assert!(!service("2001:db8::1").attempt(&store).allowed);
+    assert!(store.is_empty());
+
+    let counted = service("192.0.2.1");
+    assert!(counted.attempt(&store).allowed);
+    assert!(!counted.attempt(&store).allowed);
+    assert_eq!(access.get("192.0.2.1".parse().unwrap()), None);
+
+    // Generic string keys are never taken for addresses.
+    let username = ThrottlesService::for_key(&("10.1.2.3", "alice"), 1, period, "test_")
+        .with_access_list(access.clone());
+    assert!(username.attempt(&store).allowed);
+    assert!(!username.attempt(&store).allowed);
+    let token = ThrottlesService::for_key("203.0.113.8", 1, period, "test_")
+        .with_access_list(access.clone());
+    assert!(token.attempt(&store).allowed);
+
+    access.reload(AccessRules::new().deny("10.1.0.0/16".parse().unwrap()));
+    assert!(!allowed.attempt(&store).allowed);
+    assert!(service("10.2.0.1").attempt(&store).allowed);
+    assert_eq!(access.get("10.1.0.1".parse().unwrap()), Some(Access::Deny));
+
+    let user = ThrottlesService::for_key(&42u64, 1, period, "test_").with_access_list(access);
+    assert!(user.attempt(&store).allowed);
+    assert!(!user.attempt(&store).allowed);
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
 
 #[test]
+fn test_client_ip_behind_trusted_proxies() {
+    let proxies = TrustedProxies::new([
+        "10.0.0.0/8".parse().unwrap(),
+        "2001:db8:ffff::/48".parse().unwrap(),
+    ]);
+    let lb = ip("10.0.0.1");
+
+    let xff = [("x-forwarded-for", "203.0.113.9, 10.0.0.2")];
+    assert_eq!(proxies.client_ip(lb, xff), ip("203.0.113.9"));
+
+    // A client cannot spoof its address by sending its own header.
+    let spoofed = [("X-Forwarded-For", "1.2.3.4, 203.0.113.9")];
+    assert_eq!(proxies.client_ip(lb, spoofed), ip("203.0.113.9"));
+    assert_eq!(
+        proxies.client_ip(ip("203.0.113.9"), [("X-Forwarded-For", "1.2.3.4")]),
+        ip("203.0.113.9")
+    );
+
+    let repeated = [
+        ("X-Forwarded-For", "198.51.100.7"),
+        ("X-Forwarded-For", "10.0.0.3"),
+    ];
+    assert_eq!(proxies.client_ip(lb, repeated), ip("198.51.100.7"));
+
+    // Headers the proxies do not write come from the client and are ignored.
+    let forged = [
+        ("Forwarded", "for=1.2.3.4"),
+        ("X-Real-IP", "1.2.3.4"),
+        ("X-Forwarded-For", "9.9.9.9"),
+    ];
+    assert_eq!(proxies.client_ip(ip("10.0.0.5"), forged), ip("9.9.9.9"));
+    assert_eq!(proxies.client_ip(lb, [("X-Real-IP", "1.2.3.4")]), lb);
+
+    let rfc7239 = proxies.clone().with_header(ForwardedHeader::Forwarded);
+    let forwarded = [
+        ("Forwarded", "for=192.0.2.60;proto=http;by=10.0.0.1"),
+        ("Forwarded", r#"For="[2001:db8:ffff::17]:4711""#),
+        ("X-Forwarded-For", "198.51.100.7"),
+    ];
+    assert_eq!(rfc7239.client_ip(lb, forwarded), ip("192.0.2.60"));
+
+    let real_ip = [("X-Real-IP", "::ffff:192.0.2.1")];
+    let nginx = proxies.clone().with_header(ForwardedHeader::XRealIp);
+    assert_eq!(nginx.client_ip(lb, real_ip), ip("192.0.2.1"));
+    assert_eq!(proxies.client_ip(lb, []), lb);
+
+    // Unknown hops stop the walk at the last trusted proxy.
+    let hidden = [("Forwarded", "for=192.0.2.60, for=_hidden, for=10.0.0.9")];
+    assert_eq!(rfc7239.client_ip(lb, hidden), ip("10.0.0.9"));
+    let garbage = [("X-Forwarded-For", "not-an-ip")];
+    assert_eq!(proxies.client_ip(lb, garbage), lb);
+}
+
+#[test]
+fn test_rate_limit_headers() {
+    let (clock, store, service) = manual("test_headers", 2, Duration::from_secs(60));
+
+    let decision = service.attempt(&store);
+    assert_eq!(
+        service.headers(&decision, HeaderDialect::Ietf),
+        [
+            ("RateLimit-Policy", "\"default\";q=2;w=60".to_string()),
+            ("RateLimit", "\"default\";r=1;t=60".to_string()),
+        ]
+    );
+
+    clock.advance(Duration::from_millis(20_500));
+    let _ = service.attempt(&store);
+    let decision = service.attempt(&store);
+    assert_eq!(
+        service.headers(&decision, HeaderDialect::Legacy),
+        [
+            ("X-RateLimit-Limit", "2".to_string()),
+            ("X-RateLimit-Remaining", "0".to_string()),
+            ("X-RateLimit-Reset", "40".to_string()),
+            ("Retry-After", "40".to_string()),
+        ]
+    );
+    assert_eq!(service.headers(&decision, HeaderDialect::Both).len(), 6);
+
+    // Buckets advertise their sustained rate, not their burst size.
+    let service = ThrottlesService::new("bucket".to_string(), 10, Duration::from_secs(1), "test_")
+        .with_clock(clock.clone())
+        .with_algorithm(Algorithm::TokenBucket { capacity: 50 });
+    let decision = service.attempt(&store);
+    let headers = service.headers(&decision, HeaderDialect::Both);
+    assert_eq!(
+        headers[0],
+        ("RateLimit-Policy", "\"default\";q=10;w=1".to_string())
+    );
+    assert_eq!(headers[2], ("X-RateLimit-Limit", "50".to_string()));
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_layer() {
+    use throttle_ro::tower::{Rejection, ThrottleLayer};
+    use tower::{Layer, ServiceExt, service_fn};
+
+    let handler = service_fn(|_: http::Request<String>| async {
+        Ok::<_, std::convert::Infallible>(http::Response::new("ok".to_string()))
+    });
+    let by_client = |request: &http::Request<String>| {
+        request
+            .headers()
+            .get("x-client")
+            .and_then(|client| client.to_str().ok())
+            .map(str::to_string)
+    };
+    let request = |client: Option<&str>| {
+        let mut request = http::Request::builder();
+        if let Some(client) = client {
+            request = request.header("x-client", client);
+        }
+        request.body(String::new()).unwrap()
+    };
+
+    let layer = ThrottleLayer::new(
+        MemoryStore::new(),
+        1,
+        Duration::from_secs(60),
+        "test_",
+        by_client,
+    );
+    let service = layer.layer(handler);
+
+    let response = service.clone().oneshot(request(Some("a"))).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.body(), "ok");
+
+    let response = service.clone().oneshot(request(Some("a"))).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "60");
+    assert!(response.body().is_empty());
+
+    let response = service.clone().oneshot(request(Some("b"))).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    for _ in 0..3 {
+        let response = service.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    // Store errors are counted across the services the layer builds.
+    let failing = ThrottleLayer::new(FailingStore, 1, Duration::from_secs(60), "test_", by_client)
+        .with_failure_policy(FailurePolicy::Open);
+    let response = failing
+        .layer(handler)
+        .oneshot(request(Some("a")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(failing.failures(), 1);
+
+    let grpc = ThrottleLayer::new(
+        MemoryStore::new(),
+        0,
+        Duration::from_secs(60),
+        "test_",
+        by_client,
+    )
+    .with_rejection(Rejection::Grpc)
+    .layer(handler);
+    let response = grpc.oneshot(request(Some("a"))).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.headers()["grpc-status"], "8");
+    assert_eq!(response.headers()["content-type"], "application/grpc");
+
+    let with_headers = ThrottleLayer::new(
+        MemoryStore::new(),
+        1,
+        Duration::from_secs(60),
+        "test_",
+        by_client,
+    )
+    .with_headers(HeaderDialect::Legacy)
+    .layer(handler);
+    let response = with_headers
+        .clone()
+        .oneshot(request(Some("a")))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    let response = with_headers.oneshot(request(Some("a"))).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "1");
+    assert_eq!(response.headers()["retry-after"], "60");
+}
+
+#[cfg(feature = "actix")]
+#[actix_web::test]
+async fn test_actix_middleware() {
+    use actix_web::{App, HttpResponse, test, web};
+    use throttle_ro::actix::Throttle;
+
+    let period = Duration::from_secs(60);
+    let throttle = Throttle::new(MemoryStore::new(), 2, period, "test_")
+        .with_route("/login", 1, period)
+        .with_trusted_proxies(TrustedProxies::new(["10.0.0.0/8".parse().unwrap()]))
+        .with_headers(HeaderDialect::Legacy);
+    let app = test::init_service(
+        App::new()
+            .wrap(throttle)
+            .route("/", web::get().to(HttpResponse::Ok))
+            .route("/login", web::post().to(HttpResponse::Ok))
+            .route("/login-history", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let request = |method: test::TestRequest, path: &str, peer: &str| {
+        method
+            .uri(path)
+            .peer_addr(format!("{peer}:4000").parse().unwrap())
+            .to_request()
+    };
+
+    let response =
+        test::call_service(&app, request(test::TestRequest::get(), "/", "192.0.2.1")).await;
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get("x-ratelimit-remaining").unwrap(),
+        "1"
+    );
+
+    let login = || request(test::TestRequest::post(), "/login", "192.0.2.1");
+    assert!(
+        test::call_service(&app, login())
+            .await
+            .status()
+            .is_success()
+    );
+    let response = test::call_service(&app, login()).await;
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+    assert_eq!(response.headers().get("x-ratelimit-limit").unwrap(), "1");
+
+    // Percent-encoding the path does not escape the route's limit.
+    for path in ["/%6Cogin", "/%6cogin"] {
+        let response =
+            test::call_service(&app, request(test::TestRequest::post(), path, "192.0.2.1")).await;
+        assert_eq!(response.status(), 429, "{path}");
+    }
+
+    // Routes match whole segments, so `/login-history` gets the default policy.
+    let history = || request(test::TestRequest::get(), "/login-history", "192.0.2.3");
+    let response = test::call_service(&app, history()).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-ratelimit-limit").unwrap(), "2");
+
+    // The login route is counted apart from the default policy.
+    let response =
+        test::call_service(&app, request(test::TestRequest::get(), "/", "192.0.2.1")).await;
+    assert!(response.status().is_success());
+    let response =
+        test::call_service(&app, request(test::TestRequest::get(), "/", "192.0.2.1")).await;
+    assert_eq!(response.status(), 429);
+
+    let forwarded = test::TestRequest::post()
+        .uri("/login")
+        .peer_addr("10.0.0.1:4000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", "192.0.2.2"))
+        .to_request();
+    assert!(
+        test::call_service(&app, forwarded)
+            .await
+            .status()
+            .is_success()
+    );
+    let response = test::call_service(
+        &app,
+        request(test::TestRequest::post(), "/login", "192.0.2.2"),
+    )
+    .await;
+    assert_eq!(response.status(), 429);
+
+    // Store errors are counted across every route.
+    let failing = Throttle::new(FailingStore, 2, period, "test_")
+        .with_route("/login", 1, period)
+        .with_failure_policy(FailurePolicy::Open);
+    let app = test::init_service(
+        App::new()
+            .wrap(failing.clone())
+            .route("/", web::get().to(HttpResponse::Ok))
+            .route("/login", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+    let response =
+        test::call_service(&app, request(test::TestRequest::get(), "/", "192.0.2.1")).await;
+    assert!(response.status().is_success());
+    let response = test::call_service(
+        &app,
+        request(test::TestRequest::post(), "/login", "192.0.2.1"),
+    )
+    .await;
+    assert!(response.status().is_success());
+    assert_eq!(failing.failures(), 2);
+}
+
+/// An async store that yields to the runtime before every operation.
+#[cfg(feature = "async")]
+struct YieldingStore(MemoryStore);
+
+#[cfg(feature = "async")]
+impl throttle_ro::AsyncThrottleStore for YieldingStore {
+    async fn get<V: StoreValue>(&self, key: &str) -> Result<Option<V>, StoreError> {
+        tokio::task::yield_now().await;
+        ThrottleStore::get(&self.0, key)
+    }
+
+    async fn set<V: StoreValue>(
+        &self,
+        key: &str,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), StoreError> {
+        tokio::task::yield_now().await;
+        ThrottleStore::set(&self.0, key, value, ttl)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, StoreError> {
+        tokio::task::yield_now().await;
+        ThrottleStore::ttl(&self.0, key)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), StoreError> {
+        tokio::task::yield_now().await;
+        ThrottleStore::remove(&self.0, key)
+    }
+
+    async fn update<V, R, F>(&self, key: &str, f: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R) + Send,
+    {
+        tokio::task::yield_now().await;
+        ThrottleStore::update(&self.0, key, f)
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_async_api() {
+    let store = Arc::new(YieldingStore(MemoryStore::new()));
+    let service = Arc::new(ThrottlesService::new(
+        "127.0.0.1".to_string(),
+        100,
+        Duration::from_secs(60),
+        "test_",
+    ));
+
+    let tasks: Vec<_> = (0..200)
+        .map(|_| {
+            let (store, service) = (store.clone(), service.clone());
+            tokio::spawn(async move { service.attempt_async(&*store).await.allowed })
+        })
+        .collect();
+    let mut admitted = 0;
+    for task in tasks {
+        admitted += task.await.unwrap() as u32;
+    }
+    assert_eq!(admitted, 100);
+    assert!(!service.check_async(&*store).await.allowed);
+
+    service.remove_async(&*store).await;
+    service.hit_async(&*store).await;
+    assert_eq!(service.check_async(&*store).await.remaining, 99);
+    service.hit_n_async(&*store, 9).await;
+    let decision = service.attempt_with_cost_async(&*store, 40).await;
+    assert_eq!(decision.remaining, 50);
+
+    // Synchronous stores work with the async API as they are.
+    let memory = MemoryStore::new();
+    let service =
+        ThrottlesService::new("127.0.0.1".to_string(), 1, Duration::from_secs(60), "test_")
+            .with_algorithm(Algorithm::Gcra { burst: 1 });
+    assert!(service.attempt_async(&memory).await.allowed);
+    assert!(!service.attempt_async(&memory).await.allowed);
+}
+
+#[test]
+fn test_wait_until_ready() {
+    let store = MemoryStore::new();
+    let period = Duration::from_millis(200);
+    let service = ThrottlesService::new("127.0.0.1".to_string(), 1, period, "test_");
+
+    assert!(service.wait_until_ready(&store, Wait::new()).allowed);
+    let started = Instant::now();
+    let wait = Wait::new().timeout(Duration::from_millis(50));
+    let decision = service.wait_until_ready(&store, wait);
+    assert!(!decision.allowed);
+    assert!(decision.retry_after.is_some());
+    assert!(started.elapsed() < Duration::from_millis(50));
+
+    let wait = Wait::new().jitter(Duration::from_millis(20));
+    assert!(service.wait_until_ready(&store, wait).allowed);
+    assert!(started.elapsed() >= Duration::from_millis(150));
+
+    // Blocked keys are never admitted, so they are not waited for.
+    let access = AccessList::new(AccessRules::new().deny("127.0.0.0/8".parse().unwrap()));
+    let blocked =
+        ThrottlesService::new("127.0.0.1".to_string(), 1, period, "test_").with_access_list(access);
+    let started = Instant::now();
+    assert!(!blocked.wait_until_ready(&store, Wait::new()).allowed);
+    assert!(started.elapsed() < Duration::from_millis(50));
+}
+
+/// A store whose disk is always full.
+struct FailingStore;
+
+impl FailingStore {
+    fn error() -> StoreError {
+        ThrottleError::Storage(Box::new(std::io::Error::new(
+            std::io::ErrorKind::StorageFull,
+            "no space left on device",
+        )))
+    }
+}
+
+impl ThrottleStore for FailingStore {
+    fn get<V: StoreValue>(&self, _: &str) -> Result<Option<V>, StoreError> {
+        Err(Self::error())
+    }
+
+    fn set<V: StoreValue>(&self, _: &str, _: V, _: Duration) -> Result<(), StoreError> {
+        Err(Self::error())
+    }
+
+    fn ttl(&self, _: &str) -> Result<Option<Duration>, StoreError> {
+        Err(Self::error())
+    }
+
+    fn remove(&self, _: &str) -> Result<(), StoreError> {
+        Err(Self::error())
+    }
+
+    fn update<V, R, F>(&self, _: &str, _: F) -> Result<R, StoreError>
+    where
+        V: StoreValue,
+        F: FnMut(Option<V>, Option<Duration>) -> (Option<(V, Duration)>, R),
+    {
+        Err(Self::error())
+    }
+}
+
+#[test]
+fn test_try_methods_return_store_errors() {
+    let store = FailingStore;
+    let period = Duration::from_secs(60);
+    let service = ThrottlesService::new("127.0.0.1".to_string(), 5, period, "test_")
+        .with_algorithm(Algorithm::SlidingWindowLog);
+
+    let error = service.try_attempt(&store).unwrap_err();
+    assert!(matches!(error, ThrottleError::Storage(_)));
+    assert_eq!(
+        error.to_string(),
+        "throttle store failed: no space left on device"
+    );
+    let source = std::error::Error::source(&error).unwrap();
+    assert!(source.downcast_ref::<std::io::Error>().is_some());
+
+    assert!(service.try_check(&store).is_err());
+    assert!(service.try_hit(&store).is_err());
+    assert!(service.try_remove(&store).is_err());
+    assert!(service.try_get_expire(&store).is_err());
+
+    let tiered = TieredThrottle::new("127.0.0.1".to_string(), &[(5, period)], "test_");
+    assert!(tiered.try_attempt(&store).is_err());
+    assert!(tiered.try_hit(&store).is_err());
+
+    let boxed: Box<dyn std::error::Error + Send + Sync> = "connection reset".into();
+    assert!(matches!(
+        ThrottleError::from(boxed),
+        ThrottleError::Storage(_)
+    ));
+
+    // Errors are thread-safe, so they can cross task boundaries.
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<ThrottleError>();
+
+    let memory = MemoryStore::new();
+    assert!(service.try_attempt(&memory).unwrap().allowed);
+}
+
+#[test]
+fn test_failure_policies() {
+    let store = FailingStore;
+    let period = Duration::from_secs(60);
+    let alerts = Arc::new(AtomicU32::new(0));
+    let service = |policy| {
+        let alerts = alerts.clone();
+        ThrottlesService::new("127.0.0.1".to_string(), 5, period, "test_")
+            .with_failure_policy(policy)
+            .with_failure_hook(move |error| {
+                assert!(matches!(error, ThrottleError::Storage(_)));
+                alerts.fetch_add(1, Ordering::Relaxed);
+            })
+    };
+
+    let mut open = service(FailurePolicy::Open);
+    let decision = open.attempt(&store);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 5);
+    assert!(open.can_go(&store));
+    open.hit(&store);
+    open.remove(&store);
+    assert_eq!(open.get_expire(&store), period);
+
+    let closed = service(FailurePolicy::Closed);
+    let decision = closed.clone().attempt(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(period));
+    assert!(!closed.check(&store).allowed);
+
+    assert_eq!(open.failures(), 5);
+    assert_eq!(closed.failures(), 2);
+    assert_eq!(alerts.load(Ordering::Relaxed), 7);
+
+    let tiered = TieredThrottle::new(
+        "127.0.0.1".to_string(),
+        &[(5, period), (50, period)],
+        "test_",
+    )
+    .with_failure_policy(FailurePolicy::Closed)
+    .with_failure_hook({
+        let alerts = alerts.clone();
+        move |_| {
+            alerts.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    assert!(!tiered.attempt(&store).allowed);
+    assert_eq!(alerts.load(Ordering::Relaxed), 9);
+    assert_eq!(tiered.failures(), 2);
+
+    // Errors never reach the policy through the `try_` methods.
+    assert!(open.try_attempt(&store).is_err());
+    assert_eq!(open.failures(), 5);
+}
+
+#[test]
+#[should_panic(expected = "no space left on device")]
+fn test_failure_policy_panics_by_default() {
+    let service =
+        ThrottlesService::new("127.0.0.1".to_string(), 5, Duration::from_secs(60), "test_");
+    let _ = service.attempt(&FailingStore);
+}
+
+#[test]
+fn test_lockout_escalates_and_decays() {
+    let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let store = MemoryStore::with_clock(clock.clone());
+    let minute = Duration::from_secs(60);
+    let lockout = Lockout::for_key(&"alice", 3, Duration::from_secs(900), "login_")
+        .with_penalties(&[minute, 5 * minute, 30 * minute])
+        .with_decay(Duration::from_secs(86400))
+        .with_clock(clock.clone());
+
+    assert_eq!(lockout.record_failure(&store).remaining, 2);
+    assert_eq!(lockout.record_failure(&store).remaining, 1);
+    let decision = lockout.record_failure(&store);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, Some(minute));
+
+    // Failures during a lockout are rejected without being counted.
+    clock.advance(Duration::from_secs(30));
+    let decision = lockout.record_failure(&store);
+    assert_eq!(decision.retry_after, Some(Duration::from_secs(30)));
+    assert_eq!(
+        lockout.check(&store).retry_after,
+        Some(Duration::from_secs(30))
+    );
+
+    // Each further lockout lasts longer, up to the last penalty.
+    for penalty in [5 * minute, 30 * minute, 30 * minute] {
+        clock.advance(Duration::from_secs(30 * 60));
+        assert!(lockout.check(&store).allowed);
+        let _ = lockout.record_failure(&store);
+        let _ = lockout.record_failure(&store);
+        assert_eq!(lockout.record_failure(&store).retry_after, Some(penalty));
+    }
+
+    // A success clears the failures but not the escalation level.
+    clock.advance(Duration::from_secs(30 * 60));
+    let _ = lockout.record_failure(&store);
+    lockout.record_success(&store);
+    assert_eq!(lockout.check(&store).remaining, 3);
+    for _ in 0..3 {
+        let _ = lockout.record_failure(&store);
+    }
+    assert_eq!(lockout.check(&store).retry_after, Some(30 * minute));
+
+    // The level decays once no failure was recorded for a day.
+    clock.advance(Duration::from_secs(86400));
+    for _ in 0..3 {
+        let _ = lockout.record_failure(&store);
+    }
+    assert_eq!(lockout.check(&store).retry_after, Some(minute));
+
+    // A success does not postpone the decay.
+    lockout.reset(&store);
+    let _ = lockout.record_failure(&store);
+    clock.advance(Duration::from_secs(3600));
+    lockout.record_success(&store);
+    assert_eq!(
+        store.ttl(&lockout.key()).unwrap(),
+        Some(Duration::from_secs(86400 - 3600))
+    );
+    assert_eq!(lockout.check(&store).remaining, 3);
+
+    // Windows and penalties too long to represent saturate.
+    let forever = Lockout::for_key(&"bob", 2, Duration::MAX, "login_")
+        .with_penalties(&[Duration::MAX])
+        .with_clock(clock.clone());
+    assert_eq!(forever.record_failure(&store).remaining, 1);
+    let decision = forever.record_failure(&store);
+    assert!(!decision.allowed);
+    forever.record_success(&store);
+    assert!(!forever.check(&store).allowed);
+
+    // Failures outside the window do not add up.
+    lockout.reset(&store);
+    for _ in 0..2 {
+        let _ = lockout.record_failure(&store);
+        clock.advance(Duration::from_secs(600));
+    }
+    assert!(lockout.record_failure(&store).allowed);
+    assert_eq!(lockout.check(&store).remaining, 2);
+
+    // Usernames that look like an allow-listed address still get locked out.
+    let access = AccessList::new(AccessRules::new().allow("10.0.0.0/8".parse().unwrap()));
+    let username = Lockout::for_key("10.0.0.1", 3, minute, "login_")
+        .with_access_list(access.clone())
+        .with_clock(clock.clone());
+    for _ in 0..3 {
+        let _ = username.record_failure(&store);
+    }
+    assert!(!username.check(&store).allowed);
+    let address = Lockout::new("10.0.0.1".to_string(), 3, minute, "login_ip_")
+        .with_access_list(access)
+        .with_clock(clock.clone());
+    for _ in 0..3 {
+        let _ = address.record_failure(&store);
+    }
+    assert!(address.check(&store).allowed);
+
+    // Store errors follow the failure policy.
+    let closed = Lockout::new("127.0.0.1".to_string(), 3, minute, "login_")
+        .with_failure_policy(FailurePolicy::Closed);
+    assert!(!closed.check(&FailingStore).allowed);
+    assert!(closed.try_record_failure(&FailingStore).is_err());
+    assert_eq!(closed.failures(), 1);
+}
+
+#[test]
+fn test_login_guard_counters() {
+    let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let store = MemoryStore::with_clock(clock.clone());
+    let hour = Duration::from_secs(3600);
+    let guard = LoginGuard::new("login_")
+        .with_limit(LoginCounter::Username, 4, hour)
+        .with_limit(LoginCounter::Ip, 3, hour)
+        .with_limit(LoginCounter::UsernameIp, 2, hour)
+        .with_clock(clock.clone());
+    let (a, b, c) = (ip("203.0.113.1"), ip("203.0.113.2"), ip("203.0.113.3"));
+
+    // One address guessing one account hits the per-pair limit first.
+    guard.record_failure(&store, "alice", a);
+    guard.record_failure(&store, "alice", a);
+    let decision = guard.check(&store, "alice", a);
+    assert_eq!(decision.rejected_by, Some(LoginCounter::UsernameIp));
+    assert_eq!(decision.get(LoginCounter::Ip).unwrap().remaining, 1);
+    assert!(guard.check(&store, "bob", a).allowed);
+
+    // Guesses spread over addresses still add up on the account.
+    guard.record_failure(&store, "alice", b);
+    guard.record_failure(&store, "alice", c);
+    let decision = guard.check(&store, "alice", ip("198.51.100.1"));
+    assert_eq!(decision.rejected_by, Some(LoginCounter::Username));
+
+    // One address trying many accounts hits the per-address limit.
+    guard.record_failure(&store, "bob", a);
+    let decision = guard.check(&store, "carol", a);
+    assert_eq!(decision.rejected_by, Some(LoginCounter::Ip));
+    assert_eq!(decision.counters.len(), 3);
+
+    // A success clears the account counters but not the address one.
+    guard.record_success(&store, "alice", b);
+    assert!(guard.check(&store, "alice", b).allowed);
+    assert_eq!(
+        guard.check(&store, "carol", a).rejected_by,
+        Some(LoginCounter::Ip)
+    );
+
+    let guard = guard.with_clear_on_success(&[LoginCounter::Ip]);
+    guard.record_success(&store, "carol", a);
+    assert!(guard.check(&store, "carol", a).allowed);
+
+    clock.advance(hour);
+    assert!(guard.check(&store, "alice", a).allowed);
+
+    // Counter prefixes are encoded like any other key, so they cannot collide
+    // with a service whose prefix spells out the same characters.
+    let guard = LoginGuard::new("login_").with_limit(LoginCounter::Ip, 1, hour);
+    let service = ThrottlesService::for_ip(a, 1, hour, "login_ip_").with_clock(clock.clone());
+    guard.record_failure(&store, "dave", a);
+    assert!(service.check(&store).allowed);
+
+    // Usernames that look like an allow-listed address are still counted.
+    let access = AccessList::new(AccessRules::new().allow("10.0.0.0/8".parse().unwrap()));
+    for counter in [LoginCounter::Username, LoginCounter::UsernameIp] {
+        let guard = LoginGuard::new("spoof_")
+            .with_limit(counter, 1, hour)
+            .with_access_list(access.clone());
+        guard.record_failure(&store, "10.0.0.1", a);
+        let decision = guard.check(&store, "10.0.0.1", a);
+        assert_eq!(decision.rejected_by, Some(counter));
+    }
+
+    // Store errors follow the failure policy.
+    let closed = LoginGuard::new("login_")
+        .with_failure_policy(FailurePolicy::Closed)
+        .with_limit(LoginCounter::Username, 4, hour);
+    assert!(!closed.check(&FailingStore, "alice", a).allowed);
+    assert!(
+        closed
+            .try_record_failure(&FailingStore, "alice", a)
+            .is_err()
+    );
+    assert_eq!(closed.failures(), 1);
+}
+
+#[test]
+#[cfg(feature = "cache-ro")]
 fn test_all() {
     test_initial_can_go_is_true();
     test_hit_increments_value();
     test_can_go_blocks_after_max_attempts();
     test_remove_clears_cache();
     test_expire_returns_default_when_none_set();
-    test_expire_returns_custom_when_set()
+    test_expire_returns_custom_when_set();
+    test_attempt_blocks_after_max_attempts();
+    test_attempt_is_atomic_under_contention();
+    test_decision_reports_quota_and_retry_after();
+    #[cfg(feature = "tokio")]
+    test_until_ready_waits_out_delay();
 }
+#[cfg(feature = "cache-ro")]
 fn test_initial_can_go_is_true() {
     let ip = "127.0.0.1".to_string();
     let cache = Cache::new(CacheConfig {
@@ -25,7 +1424,7 @@ fn test_initial_can_go_is_true() {
     Cache::drop()
 }
 
-
+#[cfg(feature = "cache-ro")]
 fn test_hit_increments_value() {
     let ip = "127.0.0.2".to_string();
     let cache = Cache::new(CacheConfig {
@@ -44,6 +1443,7 @@ fn test_hit_increments_value() {
     Cache::drop()
 }
 
+#[cfg(feature = "cache-ro")]
 fn test_can_go_blocks_after_max_attempts() {
     let ip = "127.0.0.3".to_string();
     let cache = Cache::new(CacheConfig {
@@ -60,7 +1460,7 @@ fn test_can_go_blocks_after_max_attempts() {
     Cache::drop()
 }
 
-
+#[cfg(feature = "cache-ro")]
 fn test_remove_clears_cache() {
     let ip = "127.0.0.4".to_string();
     let cache = Cache::new(CacheConfig {
@@ -78,6 +1478,7 @@ fn test_remove_clears_cache() {
     Cache::drop()
 }
 
+#[cfg(feature = "cache-ro")]
 fn test_expire_returns_default_when_none_set() {
     let ip = "127.0.0.5".to_string();
     let cache = Cache::new(CacheConfig {
@@ -92,6 +1493,7 @@ fn test_expire_returns_default_when_none_set() {
     Cache::drop()
 }
 
+#[cfg(feature = "cache-ro")]
 fn test_expire_returns_custom_when_set() {
     let ip = "127.0.0.6".to_string();
     let cache = Cache::new(CacheConfig {
@@ -107,3 +1509,126 @@ fn test_expire_returns_custom_when_set() {
     assert!(service.can_go(&cache));
     Cache::drop()
 }
+
+#[cfg(feature = "cache-ro")]
+fn test_attempt_blocks_after_max_attempts() {
+    let ip = "127.0.0.7".to_string();
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+    let service = ThrottlesService::new(ip, 2, Duration::from_secs(60), "test_");
+
+    assert!(service.attempt(&cache).allowed);
+    assert!(service.attempt(&cache).allowed);
+    assert!(!service.attempt(&cache).allowed);
+    assert_eq!(cache.get::<u32>(&service.key()), Some(2));
+    Cache::drop()
+}
+
+#[cfg(feature = "cache-ro")]
+fn test_attempt_is_atomic_under_contention() {
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+    let allowed = Arc::new(AtomicU32::new(0));
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let cache = cache.clone();
+            let allowed = allowed.clone();
+            thread::spawn(move || {
+                let service = ThrottlesService::new(
+                    "127.0.0.8".to_string(),
+                    50,
+                    Duration::from_secs(60),
+                    "test_",
+                );
+                for _ in 0..20 {
+                    if service.attempt(&cache).allowed {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(allowed.load(Ordering::SeqCst), 50);
+    let service = ThrottlesService::new(
+        "127.0.0.8".to_string(),
+        50,
+        Duration::from_secs(60),
+        "test_",
+    );
+    assert_eq!(cache.get::<u32>(&service.key()), Some(50));
+    Cache::drop()
+}
+
+#[cfg(feature = "cache-ro")]
+fn test_decision_reports_quota_and_retry_after() {
+    let ip = "127.0.0.9".to_string();
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+    let service = ThrottlesService::new(ip, 2, Duration::from_secs(60), "test_");
+
+    let status = service.check(&cache);
+    assert!(status.allowed);
+    assert_eq!(status.limit, 2);
+    assert_eq!(status.remaining, 2);
+    assert_eq!(status.reset_after, Duration::from_secs(60));
+    assert_eq!(status.retry_after, None);
+
+    assert_eq!(service.attempt(&cache).remaining, 1);
+    assert_eq!(service.attempt(&cache).remaining, 0);
+
+    let status = service.attempt(&cache);
+    assert!(!status.allowed);
+    assert_eq!(status.remaining, 0);
+    assert!(status.reset_after <= Duration::from_secs(60));
+    assert_eq!(status.retry_after, Some(status.reset_after));
+    assert!(!service.check(&cache).allowed);
+    Cache::drop()
+}
+
+#[cfg(all(feature = "cache-ro", feature = "tokio"))]
+fn test_until_ready_waits_out_delay() {
+    let ip = "127.0.0.15".to_string();
+    let cache = Cache::new(CacheConfig {
+        persistent: false,
+        ..Default::default()
+    })
+    .unwrap();
+    let service = ThrottlesService::new(ip, 10, Duration::from_secs(1), "test_")
+        .with_algorithm(Algorithm::LeakyBucket { max_queue: 1 });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let started = std::time::Instant::now();
+        assert!(service.until_ready(&cache).await.allowed);
+        assert!(service.until_ready(&cache).await.allowed);
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        assert!(service.attempt(&cache).allowed);
+        let started = std::time::Instant::now();
+        let wait = Wait::new().timeout(Duration::from_millis(10));
+        assert!(!service.until_ready_with(&cache, wait).await.allowed);
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        // Without a deadline, the rejection is waited out.
+        assert!(service.until_ready(&cache).await.allowed);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    });
+    Cache::drop()
+}
@@ -0,0 +1,121 @@
+//! Runs against the Redis server at `REDIS_URL` (default `redis://127.0.0.1/`):
+//!
+//! ```text
+//! redis-server --daemonize yes
+//! cargo test --features redis --test redis -- --ignored
+//! ```
+#![cfg(feature = "redis")]
+
+use std::thread;
+use std::time::Duration;
+use throttle_ro::store::RedisStore;
+use throttle_ro::{Algorithm, ThrottleStore, ThrottlesService};
+
+fn store() -> RedisStore {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+    RedisStore::open(&url).unwrap()
+}
+
+fn service(name: &str, max_attempts: u32, algorithm: Algorithm) -> ThrottlesService {
+    let prefix = format!("throttle_ro_test_{name}_");
+    ThrottlesService::new(
+        "127.0.0.1".to_string(),
+        max_attempts,
+        Duration::from_secs(60),
+        &prefix,
+    )
+    .with_algorithm(algorithm)
+}
+
+#[test]
+#[ignore = "requires a running redis-server"]
+fn test_fixed_window_script() {
+    let store = store();
+    let mut service = service("fixed_window", 2, Algorithm::FixedWindow);
+    service.remove(&store);
+
+    assert_eq!(service.check(&store).remaining, 2);
+    assert_eq!(service.attempt(&store).remaining, 1);
+    service.hit(&store);
+    assert_eq!(store.get::<u32>(&service.key()).unwrap(), Some(2));
+
+    let decision = service.attempt(&store);
+    assert!(!decision.allowed);
+    assert!(decision.retry_after.unwrap() <= Duration::from_secs(60));
+    service.remove(&store);
+}
+
+#[test]
+#[ignore = "requires a running redis-server"]
+fn test_token_bucket_script() {
+    let store = store();
+    let service = service("token_bucket", 1, Algorithm::TokenBucket { capacity: 3 });
+    service.remove(&store);
+
+    assert_eq!(service.attempt(&store).remaining, 2);
+    assert!(service.attempt(&store).allowed);
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+
+    // Hits go through the script too, so every write is stamped with server time.
+    service.remove(&store);
+    service.hit_n(&store, 3);
+    let decision = service.check(&store);
+    assert_eq!(decision.remaining, 0);
+    assert!(decision.retry_after.unwrap() <= Duration::from_secs(60));
+    service.remove(&store);
+}
+
+#[test]
+#[ignore = "requires a running redis-server"]
+fn test_scripts_weigh_costs() {
+    let store = store();
+    for algorithm in [
+        Algorithm::FixedWindow,
+        Algorithm::TokenBucket { capacity: 5 },
+    ] {
+        let service = service("weighted", 5, algorithm);
+        service.remove(&store);
+
+        assert_eq!(service.attempt_with_cost(&store, 4).remaining, 1);
+        assert!(!service.attempt_with_cost(&store, 2).allowed);
+        assert!(service.attempt(&store).allowed);
+        service.remove(&store);
+    }
+}
+
+#[test]
+#[ignore = "requires a running redis-server"]
+fn test_watch_update_for_other_algorithms() {
+    let store = store();
+    let service = service("sliding_window_log", 2, Algorithm::SlidingWindowLog);
+    service.remove(&store);
+
+    assert!(service.attempt(&store).allowed);
+    assert!(service.attempt(&store).allowed);
+    assert!(!service.attempt(&store).allowed);
+    service.remove(&store);
+}
+
+#[test]
+#[ignore = "requires a running redis-server"]
+fn test_replicas_share_one_limit() {
+    let store = store();
+    let service = service("shared", 50, Algorithm::FixedWindow);
+    service.remove(&store);
+
+    let allowed: u32 = thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                // Every thread opens its own store, like separate replicas would.
+                let store = self::store();
+                let service = &service;
+                scope.spawn(move || (0..20).filter(|_| service.attempt(&store).allowed).count())
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap() as u32).sum()
+    });
+
+    assert_eq!(allowed, 50);
+    service.remove(&store);
+}